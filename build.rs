@@ -1,13 +1,252 @@
+#[cfg(feature = "vendored")]
+extern crate cmake;
+extern crate pkg_config;
+
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where an Embree install was found
+struct Embree {
+    lib_dir: PathBuf,
+    include_dir: Option<PathBuf>,
+}
 
 fn main() {
-    println!("{:?}", env::var("EMBREE_DIR"));
-    if let Ok(e) = env::var("EMBREE_DIR") {
-        let mut embree_dir = PathBuf::from(e);
-        embree_dir.push("lib");
-        println!("cargo:rustc-link-search=native={}", embree_dir.display());
-        println!("cargo:rerun-if-env-changed=EMBREE_DIR");
-    }
-    println!("cargo:rustc-link-lib=embree3");
+    for var in &[
+        "EMBREE_LIB_DIR",
+        "EMBREE_INCLUDE_DIR",
+        "EMBREE_DIR",
+        "CMAKE_PREFIX_PATH",
+        "EMBREE_SRC_DIR",
+        "EMBREE_ISA",
+        "EMBREE_TASKING_SYSTEM",
+    ] {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+
+    #[cfg(feature = "vendored")]
+    link_vendored();
+    #[cfg(not(feature = "vendored"))]
+    link_installed();
+}
+
+#[cfg(feature = "vendored")]
+fn link_vendored() {
+    let embree = build_vendored();
+    print_search_paths(&embree);
+    link_static(&embree.lib_dir);
+}
+
+#[cfg(not(feature = "vendored"))]
+fn link_installed() {
+    let embree = find_embree();
+    // Without an install found we rely on libembree3 being on the default
+    // linker search path
+    if let Some(ref embree) = embree {
+        print_search_paths(embree);
+    }
+    if cfg!(feature = "static") {
+        let embree = embree.expect(
+            "Could not find Embree to link statically, set EMBREE_LIB_DIR \
+             to the directory containing libembree3.a",
+        );
+        link_static(&embree.lib_dir);
+    } else {
+        println!("cargo:rustc-link-lib=embree3");
+    }
+}
+
+fn print_search_paths(embree: &Embree) {
+    println!(
+        "cargo:rustc-link-search=native={}",
+        embree.lib_dir.display()
+    );
+    if let Some(ref include_dir) = embree.include_dir {
+        println!("cargo:include={}", include_dir.display());
+    }
+}
+
+/// Link the static Embree library and the internal libraries it depends on,
+/// which are installed alongside it when Embree is built with `EMBREE_STATIC_LIB`
+fn link_static(lib_dir: &Path) {
+    let target = env::var("TARGET").unwrap();
+    let msvc = target.contains("msvc");
+    // The ISA specific kernels and the core library depend on each other, so
+    // the kernels are linked whole and first to resolve the core's references
+    // to them. Only the ISAs Embree was built with are present, with AVX-512
+    // named avx512skx before Embree 3.13.
+    for isa in &[
+        "embree_sse42",
+        "embree_avx",
+        "embree_avx2",
+        "embree_avx512",
+        "embree_avx512skx",
+    ] {
+        let file = if msvc {
+            format!("{}.lib", isa)
+        } else {
+            format!("lib{}.a", isa)
+        };
+        if lib_dir.join(file).is_file() {
+            println!("cargo:rustc-link-lib=static:+whole-archive={}", isa);
+        }
+    }
+    for lib in &["embree3", "lexers", "tasking", "simd", "math", "sys"] {
+        println!("cargo:rustc-link-lib=static={}", lib);
+    }
+    if tasking_system() == "TBB" {
+        println!("cargo:rustc-link-lib=tbb");
+    }
+    if target.contains("apple") {
+        println!("cargo:rustc-link-lib=c++");
+    } else if !msvc {
+        println!("cargo:rustc-link-lib=stdc++");
+    }
+}
+
+/// The tasking system of a static or source built Embree, `INTERNAL` by
+/// default to not depend on TBB
+fn tasking_system() -> String {
+    env::var("EMBREE_TASKING_SYSTEM")
+        .map(|t| t.to_uppercase())
+        .unwrap_or_else(|_| "INTERNAL".to_string())
+}
+
+/// Build Embree as a static library from the source bundled in `vendor/embree`,
+/// or the source tree at `EMBREE_SRC_DIR` if it's set. `EMBREE_ISA` selects
+/// the ISAs to build kernels for as a comma separated list, e.g. `SSE2,AVX2`,
+/// and `EMBREE_TASKING_SYSTEM` the tasking system, `INTERNAL` or `TBB`.
+#[cfg(feature = "vendored")]
+fn build_vendored() -> Embree {
+    let src_dir = match env::var_os("EMBREE_SRC_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("vendor/embree"),
+    };
+    let cmake_lists = fs::read_to_string(src_dir.join("CMakeLists.txt")).unwrap_or_else(|_| {
+        panic!(
+            "No Embree source tree found at {}, run `git submodule update --init` \
+             or set EMBREE_SRC_DIR to an Embree 3 source tree",
+            src_dir.display()
+        )
+    });
+    println!("cargo:rerun-if-changed={}", src_dir.display());
+
+    let mut config = cmake::Config::new(&src_dir);
+    config
+        .profile("Release")
+        .define("EMBREE_STATIC_LIB", "ON")
+        .define("EMBREE_ISPC_SUPPORT", "OFF")
+        .define("EMBREE_TUTORIALS", "OFF")
+        .define("EMBREE_TASKING_SYSTEM", tasking_system());
+    if let Ok(isas) = env::var("EMBREE_ISA") {
+        // Embree before 3.13 names its AVX-512 target AVX512SKX
+        let avx512 = if cmake_lists.contains("EMBREE_ISA_AVX512SKX") {
+            "AVX512SKX"
+        } else {
+            "AVX512"
+        };
+        let isas: Vec<String> = isas
+            .split(',')
+            .map(|i| match i.trim().to_uppercase().as_str() {
+                "AVX512" | "AVX512SKX" => avx512.to_string(),
+                isa => isa.to_string(),
+            })
+            .collect();
+        for isa in &["SSE2", "SSE42", "AVX", "AVX2", avx512] {
+            let enabled = isas.iter().any(|i| i == isa);
+            config.define(
+                format!("EMBREE_ISA_{}", isa),
+                if enabled { "ON" } else { "OFF" },
+            );
+        }
+    }
+    let dst = config.build();
+    let lib = if dst.join("lib64").is_dir() {
+        "lib64"
+    } else {
+        "lib"
+    };
+    from_prefix(&dst, lib)
+}
+
+#[cfg(not(feature = "vendored"))]
+/// Find Embree from the environment variables, then pkg-config, then
+/// Embree's CMake config under `CMAKE_PREFIX_PATH`
+fn find_embree() -> Option<Embree> {
+    if let Some(lib_dir) = env::var_os("EMBREE_LIB_DIR") {
+        return Some(Embree {
+            lib_dir: PathBuf::from(lib_dir),
+            include_dir: env::var_os("EMBREE_INCLUDE_DIR").map(PathBuf::from),
+        });
+    }
+    if let Some(dir) = env::var_os("EMBREE_DIR") {
+        return Some(from_prefix(Path::new(&dir), "lib"));
+    }
+    find_pkg_config().or_else(find_cmake_config)
+}
+
+#[cfg(not(feature = "vendored"))]
+fn find_pkg_config() -> Option<Embree> {
+    // We print our own link lines, which also disables the rerun lines
+    // pkg-config prints for the variables it reads
+    let target = env::var("TARGET").unwrap();
+    for var in &[
+        "PKG_CONFIG",
+        "PKG_CONFIG_PATH",
+        "PKG_CONFIG_LIBDIR",
+        "PKG_CONFIG_SYSROOT_DIR",
+    ] {
+        println!("cargo:rerun-if-env-changed={}", var);
+        println!("cargo:rerun-if-env-changed={}_{}", var, target);
+        println!(
+            "cargo:rerun-if-env-changed={}_{}",
+            var,
+            target.replace('-', "_")
+        );
+        println!("cargo:rerun-if-env-changed=TARGET_{}", var);
+        println!("cargo:rerun-if-env-changed=HOST_{}", var);
+    }
+    let lib = pkg_config::Config::new()
+        .cargo_metadata(false)
+        .probe("embree3")
+        .ok()?;
+    // Distro .pc files often omit -L for the system library directory
+    let lib_dir = match lib.link_paths.into_iter().next() {
+        Some(dir) => dir,
+        None => PathBuf::from(pkg_config::get_variable("embree3", "libdir").ok()?),
+    };
+    Some(Embree {
+        lib_dir: lib_dir,
+        include_dir: lib.include_paths.into_iter().next(),
+    })
+}
+
+#[cfg(not(feature = "vendored"))]
+/// Look for `<prefix>/lib*/cmake/embree-<version>/embree-config.cmake`
+/// in each prefix of `CMAKE_PREFIX_PATH`
+fn find_cmake_config() -> Option<Embree> {
+    let prefixes = env::var_os("CMAKE_PREFIX_PATH")?;
+    for prefix in env::split_paths(&prefixes) {
+        for lib in &["lib", "lib64"] {
+            let entries = match fs::read_dir(prefix.join(lib).join("cmake")) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.filter_map(|e| e.ok()) {
+                let is_embree = entry.file_name().to_string_lossy().starts_with("embree");
+                if is_embree && entry.path().join("embree-config.cmake").is_file() {
+                    return Some(from_prefix(&prefix, lib));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn from_prefix(prefix: &Path, lib: &str) -> Embree {
+    Embree {
+        lib_dir: prefix.join(lib),
+        include_dir: Some(prefix.join("include")),
+    }
 }
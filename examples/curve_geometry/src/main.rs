@@ -5,81 +5,78 @@ extern crate support;
 
 use cgmath::{Vector3, Vector4};
 use embree::{
-    BezierCurve, BsplineCurve, CatmullRomCurve, Device, Geometry, HermiteCurve, IntersectContext,
-    LinearCurve, QuadMesh, Ray, RayHit, Scene,
+    BezierCurve, BsplineCurve, CatmullRomCurve, Device, EmbreeGeometry, HermiteCurve,
+    IntersectContext, LinearCurve, QuadMesh, Ray, RayHit, Scene,
 };
 use support::Camera;
 
-fn make_linear_curve<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_linear_curve<'a>(device: &'a Device) -> LinearCurve<'a> {
     let mut curve = LinearCurve::cone(&device, 2, 3, false);
     {
         let mut verts = curve.vertex_buffer.map();
         let mut ids = curve.index_buffer.map();
         let mut flags = curve.flag_buffer.map();
-        verts[0] = Vector4::new(-5.0, 0.0, 0.0, 0.35);
-        verts[1] = Vector4::new(-5.0, 4.0, -1.0, 0.25);
-        verts[2] = Vector4::new(-5.0, 8.0, 2.0, 0.05);
+        verts[0] = Vector4::new(-5.0, 0.0, 0.0, 0.35).into();
+        verts[1] = Vector4::new(-5.0, 4.0, -1.0, 0.25).into();
+        verts[2] = Vector4::new(-5.0, 8.0, 2.0, 0.05).into();
         ids[0] = 0;
         ids[1] = 1;
         flags[0] = 10;
         flags[1] = 1;
     }
-    let mut curve_geo = Geometry::LinearCurve(curve);
-    curve_geo.commit();
-    curve_geo
+    curve.commit();
+    curve
 }
 
-fn make_bspline_curve<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_bspline_curve<'a>(device: &'a Device) -> BsplineCurve<'a> {
     let mut curve = BsplineCurve::normal_oriented(&device, 4, 6);
     {
         let mut verts = curve.vertex_buffer.map();
         let mut ids = curve.index_buffer.map();
         let mut normals = curve.normal_buffer.as_mut().unwrap().map();
-        verts[0] = Vector4::new(-0.0, -0.0, -5.0, 0.3);
-        verts[1] = Vector4::new(-0.0, -0.0, -0.0, 0.5);
-        verts[2] = Vector4::new(-0.0, 8.0, 0.0, 1.0);
-        verts[3] = Vector4::new(-0.0, 5.0, 3.0, 1.0);
-        verts[4] = Vector4::new(-0.0, 10.0, 5.0, 0.55);
-        verts[5] = Vector4::new(-0.0, 5.0, 12.0, 0.02);
+        verts[0] = Vector4::new(-0.0, -0.0, -5.0, 0.3).into();
+        verts[1] = Vector4::new(-0.0, -0.0, -0.0, 0.5).into();
+        verts[2] = Vector4::new(-0.0, 8.0, 0.0, 1.0).into();
+        verts[3] = Vector4::new(-0.0, 5.0, 3.0, 1.0).into();
+        verts[4] = Vector4::new(-0.0, 10.0, 5.0, 0.55).into();
+        verts[5] = Vector4::new(-0.0, 5.0, 12.0, 0.02).into();
         ids[0] = 0;
         ids[1] = 1;
         ids[2] = 2;
         ids[3] = 3;
-        normals[0] = Vector3::new(0.1, 0.8, 0.1);
-        normals[1] = Vector3::new(0.1, 0.8, 0.1);
-        normals[2] = Vector3::new(0.1, 0.8, 0.1);
-        normals[3] = Vector3::new(0.1, 0.8, 0.1);
-        normals[4] = Vector3::new(0.1, 0.8, 0.1);
-        normals[5] = Vector3::new(0.1, 0.8, 0.1);
+        normals[0] = Vector3::new(0.1, 0.8, 0.1).into();
+        normals[1] = Vector3::new(0.1, 0.8, 0.1).into();
+        normals[2] = Vector3::new(0.1, 0.8, 0.1).into();
+        normals[3] = Vector3::new(0.1, 0.8, 0.1).into();
+        normals[4] = Vector3::new(0.1, 0.8, 0.1).into();
+        normals[5] = Vector3::new(0.1, 0.8, 0.1).into();
     }
-    let mut curve_geo = Geometry::BsplineCurve(curve);
-    curve_geo.commit();
-    curve_geo
+    curve.commit();
+    curve
 }
 
-fn make_bezier_curve<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_bezier_curve<'a>(device: &'a Device) -> BezierCurve<'a> {
     let mut curve = BezierCurve::round(&device, 2, 8, false);
     {
         let mut verts = curve.vertex_buffer.map();
         let mut ids = curve.index_buffer.map();
-        verts[0] = Vector4::new(5.0, -0.0, -5.0, 0.3);
-        verts[1] = Vector4::new(5.0, -0.0, -0.0, 0.5);
-        verts[2] = Vector4::new(5.0, 5.0, 0.0, 1.0);
-        verts[3] = Vector4::new(5.0, 5.0, 5.0, 1.0);
-        verts[4] = Vector4::new(5.0, 5.0, 10.0, 1.0);
-        verts[5] = Vector4::new(5.0, 5.0, 12.0, 0.035);
-        verts[6] = Vector4::new(5.0, 7.0, 11.0, 0.02);
-        verts[7] = Vector4::new(5.0, 10.0, 9.0, 0.01);
+        verts[0] = Vector4::new(5.0, -0.0, -5.0, 0.3).into();
+        verts[1] = Vector4::new(5.0, -0.0, -0.0, 0.5).into();
+        verts[2] = Vector4::new(5.0, 5.0, 0.0, 1.0).into();
+        verts[3] = Vector4::new(5.0, 5.0, 5.0, 1.0).into();
+        verts[4] = Vector4::new(5.0, 5.0, 10.0, 1.0).into();
+        verts[5] = Vector4::new(5.0, 5.0, 12.0, 0.035).into();
+        verts[6] = Vector4::new(5.0, 7.0, 11.0, 0.02).into();
+        verts[7] = Vector4::new(5.0, 10.0, 9.0, 0.01).into();
 
         ids[0] = 0;
         ids[1] = 3;
     }
-    let mut curve_geo = Geometry::BezierCurve(curve);
-    curve_geo.commit();
-    curve_geo
+    curve.commit();
+    curve
 }
 
-fn make_hermite_curve<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_hermite_curve<'a>(device: &'a Device) -> HermiteCurve<'a> {
     let mut curve = HermiteCurve::normal_oriented(&device, 2, 3);
     {
         let mut verts = curve.vertex_buffer.map();
@@ -87,63 +84,60 @@ fn make_hermite_curve<'a>(device: &'a Device) -> Geometry<'a> {
         let mut normals = curve.normal_buffer.as_mut().unwrap().map();
         let mut tangents = curve.tangent_buffer.map();
         let mut normal_derivatives = curve.normal_derivative_buffer.as_mut().unwrap().map();
-        verts[0] = Vector4::new(10.0, -0.0, -0.0, 0.3);
-        verts[1] = Vector4::new(10.0, 2.0, 4.0, 0.5);
-        verts[2] = Vector4::new(10.0, 8.0, 8.0, 0.2);
+        verts[0] = Vector4::new(10.0, -0.0, -0.0, 0.3).into();
+        verts[1] = Vector4::new(10.0, 2.0, 4.0, 0.5).into();
+        verts[2] = Vector4::new(10.0, 8.0, 8.0, 0.2).into();
         ids[0] = 0;
         ids[1] = 1;
-        normals[0] = Vector3::new(0.5, 0.4, 0.1);
-        normals[1] = Vector3::new(0.5, 0.4, 0.1);
-        normals[2] = Vector3::new(0.5, 0.4, 0.1);
-        tangents[0] = Vector4::new(0.0, 10.0, 0.0, 0.1);
-        tangents[1] = Vector4::new(0.0, 10.0, 0.0, 0.1);
-        tangents[2] = Vector4::new(0.0, 10.0, 0.0, 0.1);
-        normal_derivatives[0] = Vector3::new(0.4, 0.5, 1.0);
-        normal_derivatives[1] = Vector3::new(0.4, 0.5, 1.0);
-        normal_derivatives[2] = Vector3::new(0.4, 0.5, 1.0);
+        normals[0] = Vector3::new(0.5, 0.4, 0.1).into();
+        normals[1] = Vector3::new(0.5, 0.4, 0.1).into();
+        normals[2] = Vector3::new(0.5, 0.4, 0.1).into();
+        tangents[0] = Vector4::new(0.0, 10.0, 0.0, 0.1).into();
+        tangents[1] = Vector4::new(0.0, 10.0, 0.0, 0.1).into();
+        tangents[2] = Vector4::new(0.0, 10.0, 0.0, 0.1).into();
+        normal_derivatives[0] = Vector3::new(0.4, 0.5, 1.0).into();
+        normal_derivatives[1] = Vector3::new(0.4, 0.5, 1.0).into();
+        normal_derivatives[2] = Vector3::new(0.4, 0.5, 1.0).into();
     }
-    let mut curve_geo = Geometry::HermiteCurve(curve);
-    curve_geo.commit();
-    curve_geo
+    curve.commit();
+    curve
 }
 
-fn make_catmull_curve<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_catmull_curve<'a>(device: &'a Device) -> CatmullRomCurve<'a> {
     let mut curve = CatmullRomCurve::round(&device, 4, 8, false);
     {
         let mut verts = curve.vertex_buffer.map();
         let mut ids = curve.index_buffer.map();
-        verts[0] = Vector4::new(15.0, -0.0, -5.0, 0.3);
-        verts[1] = Vector4::new(15.0, -0.0, -0.0, 0.5);
-        verts[2] = Vector4::new(15.0, 3.0, 0.0, 1.0);
-        verts[3] = Vector4::new(15.0, 4.0, 5.0, 1.0);
-        verts[4] = Vector4::new(15.0, 5.0, 10.0, 1.0);
-        verts[5] = Vector4::new(15.0, 6.0, 12.0, 0.035);
-        verts[6] = Vector4::new(15.0, 7.0, 11.0, 0.02);
-        verts[7] = Vector4::new(15.0, 10.0, 9.0, 0.01);
+        verts[0] = Vector4::new(15.0, -0.0, -5.0, 0.3).into();
+        verts[1] = Vector4::new(15.0, -0.0, -0.0, 0.5).into();
+        verts[2] = Vector4::new(15.0, 3.0, 0.0, 1.0).into();
+        verts[3] = Vector4::new(15.0, 4.0, 5.0, 1.0).into();
+        verts[4] = Vector4::new(15.0, 5.0, 10.0, 1.0).into();
+        verts[5] = Vector4::new(15.0, 6.0, 12.0, 0.035).into();
+        verts[6] = Vector4::new(15.0, 7.0, 11.0, 0.02).into();
+        verts[7] = Vector4::new(15.0, 10.0, 9.0, 0.01).into();
 
         ids[0] = 0;
         ids[1] = 1;
         ids[2] = 2;
         ids[3] = 3;
     }
-    let mut curve_geo = Geometry::CatmullRomCurve(curve);
-    curve_geo.commit();
-    curve_geo
+    curve.commit();
+    curve
 }
 
-fn make_ground_plane<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_ground_plane<'a>(device: &'a Device) -> QuadMesh<'a> {
     let mut mesh = QuadMesh::unanimated(device, 1, 4);
     {
         let mut verts = mesh.vertex_buffer.map();
         let mut quads = mesh.index_buffer.map();
-        verts[0] = Vector4::new(-25.0, -2.0, -25.0, 0.0);
-        verts[1] = Vector4::new(-25.0, -2.0, 25.0, 0.0);
-        verts[2] = Vector4::new(25.0, -2.0, 25.0, 0.0);
-        verts[3] = Vector4::new(25.0, -2.0, -25.0, 1.0);
+        verts[0] = Vector4::new(-25.0, -2.0, -25.0, 0.0).into();
+        verts[1] = Vector4::new(-25.0, -2.0, 25.0, 0.0).into();
+        verts[2] = Vector4::new(25.0, -2.0, 25.0, 0.0).into();
+        verts[3] = Vector4::new(25.0, -2.0, -25.0, 1.0).into();
 
-        quads[0] = Vector4::new(0, 1, 2, 3);
+        quads[0] = Vector4::new(0, 1, 2, 3).into();
     }
-    let mut mesh = Geometry::Quad(mesh);
     mesh.commit();
     mesh
 }
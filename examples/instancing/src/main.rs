@@ -6,7 +6,7 @@ extern crate support;
 
 use cgmath::{InnerSpace, Matrix, Matrix4, SquareMatrix, Vector3, Vector4};
 use embree::{
-    Device, Geometry, Instance, IntersectContext, QuadMesh, Ray, RayHit, Scene, TriangleMesh,
+    Device, EmbreeGeometry, Instance, IntersectContext, QuadMesh, Ray, RayHit, Scene, TriangleMesh,
 };
 use std::{f32, u32};
 use support::Camera;
@@ -17,7 +17,7 @@ fn make_triangulated_sphere<'a>(
     device: &'a Device,
     pos: Vector3<f32>,
     radius: f32,
-) -> Geometry<'a> {
+) -> TriangleMesh<'a> {
     let num_phi = 5;
     let num_theta = 2 * num_phi;
     let mut mesh = TriangleMesh::unanimated(
@@ -66,23 +66,21 @@ fn make_triangulated_sphere<'a>(
             }
         }
     }
-    let mut mesh = Geometry::Triangle(mesh);
     mesh.commit();
     mesh
 }
-fn make_ground_plane<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_ground_plane<'a>(device: &'a Device) -> QuadMesh<'a> {
     let mut mesh = QuadMesh::unanimated(device, 1, 4);
     {
         let mut verts = mesh.vertex_buffer.map();
         let mut quads = mesh.index_buffer.map();
-        verts[0] = Vector4::new(-10.0, -2.0, -10.0, 0.0);
-        verts[1] = Vector4::new(-10.0, -2.0, 10.0, 0.0);
-        verts[2] = Vector4::new(10.0, -2.0, 10.0, 0.0);
-        verts[3] = Vector4::new(10.0, -2.0, -10.0, 0.0);
+        verts[0] = Vector4::new(-10.0, -2.0, -10.0, 0.0).into();
+        verts[1] = Vector4::new(-10.0, -2.0, 10.0, 0.0).into();
+        verts[2] = Vector4::new(10.0, -2.0, 10.0, 0.0).into();
+        verts[3] = Vector4::new(10.0, -2.0, -10.0, 0.0).into();
 
-        quads[0] = Vector4::new(0, 1, 2, 3);
+        quads[0] = Vector4::new(0, 1, 2, 3).into();
     }
-    let mut mesh = Geometry::Quad(mesh);
     mesh.commit();
     mesh
 }
@@ -140,7 +138,7 @@ fn main() {
 
     let mut scene = Scene::new(&device);
     for i in instances.into_iter() {
-        scene.attach_geometry(Geometry::Instance(i));
+        scene.attach_geometry(i);
     }
 
     let instance_colors = vec![
@@ -181,16 +179,15 @@ fn main() {
             *p = 0;
         }
 
-        // Update scene transformations
+        // Update scene transformations. The committed scene from the previous
+        // frame has been dropped, so we can modify the scene and commit it again
         let (transforms, normal_transforms) = animate_instances(time, num_instances);
-        let mut tfm_iter = transforms.iter();
-        for g in scene.iter_mut() {
-            if let Geometry::Instance(ref mut inst) = g.1 {
-                inst.set_transform(tfm_iter.next().expect("out of bounds tfm"));
-            }
-            // A bit annoying here that we can't call the mut on the geometry
-            // part because we borred the inner instance piece as mutable
-            g.1.commit();
+        for (i, tfm) in transforms.iter().enumerate() {
+            let inst = scene
+                .get_geometry_mut::<Instance>(i as u32)
+                .expect("instance ids should be 0-3");
+            inst.set_transform(*tfm);
+            inst.commit();
         }
 
         let rtscene = scene.commit();
@@ -9,8 +9,7 @@ extern crate tobj;
 use std::path::Path;
 
 use cgmath::{InnerSpace, Matrix3, Point2, Vector2, Vector3, Vector4};
-use embree::{Device, Geometry, IntersectContext, Ray, RayHit, Scene, TriangleMesh};
-use rand::prelude::*;
+use embree::{Device, EmbreeGeometry, Ray, RayHit, Scene, TriangleMesh};
 use rayon::prelude::*;
 use support::{Camera, AABB};
 
@@ -100,95 +99,103 @@ impl<'embree> AOIntegrator<'embree> {
         }
     }
 
-    // Simple AO computation method
-    pub fn render(&self, i: u32, j: u32, u: Point2<f32>) -> f32 {
-        let dir = self.camera.ray_dir((i as f32 + 0.5, j as f32 + 0.5));
-        let ray = Ray::new(self.camera.pos, dir);
-        let mut ray_hit = RayHit::new(ray);
-
-        let mut intersection_ctx = IntersectContext::coherent();
-        self.rtscene.intersect(&mut intersection_ctx, &mut ray_hit);
-
-        if ray_hit.hit.hit() {
-            let mesh = &self.models[self.mesh_ids[ray_hit.hit.geomID as usize] as usize].mesh;
-            // Compute the normal at the intersection point
-            let mut n = {
-                if !mesh.normals.is_empty() {
-                    // In this case, we will interpolate the normals
-                    // Note that this operation is supported by embree (internal)
-                    let prim = ray_hit.hit.primID as usize;
-                    let tri = [
-                        mesh.indices[prim * 3] as usize,
-                        mesh.indices[prim * 3 + 1] as usize,
-                        mesh.indices[prim * 3 + 2] as usize,
-                    ];
-
-                    // Retrive the different normal vectors
-                    let na = Vector3::new(
-                        mesh.normals[tri[0] * 3],
-                        mesh.normals[tri[0] * 3 + 1],
-                        mesh.normals[tri[0] * 3 + 2],
-                    );
-
-                    let nb = Vector3::new(
-                        mesh.normals[tri[1] * 3],
-                        mesh.normals[tri[1] * 3 + 1],
-                        mesh.normals[tri[1] * 3 + 2],
-                    );
-
-                    let nc = Vector3::new(
-                        mesh.normals[tri[2] * 3],
-                        mesh.normals[tri[2] * 3 + 1],
-                        mesh.normals[tri[2] * 3 + 2],
-                    );
-
-                    // Interpolate
-                    let w = 1.0 - ray_hit.hit.u - ray_hit.hit.v;
-                    (na * w + nb * ray_hit.hit.u + nc * ray_hit.hit.v).normalize()
-                } else {
-                    // As the mesh normal is not provided
-                    // we will uses the geometric normals
-                    // fortunately, embree computes this information for us
-                    Vector3::new(ray_hit.hit.Ng_x, ray_hit.hit.Ng_y, ray_hit.hit.Ng_z).normalize()
-                }
-            };
+    // Create the camera rays for an image of the given dimensions in
+    // scanline order, so each row can be traced as a coherent chunk
+    pub fn camera_rays(&self, dims: (u32, u32)) -> Vec<RayHit> {
+        (0..dims.0 * dims.1)
+            .into_par_iter()
+            .map(|i| {
+                let px = ((i % dims.0) as f32 + 0.5, (i / dims.0) as f32 + 0.5);
+                RayHit::new(Ray::new(self.camera.pos, self.camera.ray_dir(px)))
+            })
+            .collect()
+    }
+
+    // Create the AO ray for a traced camera ray, returns None
+    // if the camera ray didn't hit anything
+    pub fn ao_ray(&self, ray_hit: &RayHit, u: Point2<f32>) -> Option<RayHit> {
+        if !ray_hit.hit.hit() {
+            return None;
+        }
+        let dir = Vector3::new(ray_hit.ray.dir_x, ray_hit.ray.dir_y, ray_hit.ray.dir_z);
+        let mesh = &self.models[self.mesh_ids[ray_hit.hit.geomID as usize] as usize].mesh;
+        // Compute the normal at the intersection point
+        let mut n = {
+            if !mesh.normals.is_empty() {
+                // In this case, we will interpolate the normals
+                // Note that this operation is supported by embree (internal)
+                let prim = ray_hit.hit.primID as usize;
+                let tri = [
+                    mesh.indices[prim * 3] as usize,
+                    mesh.indices[prim * 3 + 1] as usize,
+                    mesh.indices[prim * 3 + 2] as usize,
+                ];
+
+                // Retrive the different normal vectors
+                let na = Vector3::new(
+                    mesh.normals[tri[0] * 3],
+                    mesh.normals[tri[0] * 3 + 1],
+                    mesh.normals[tri[0] * 3 + 2],
+                );
+
+                let nb = Vector3::new(
+                    mesh.normals[tri[1] * 3],
+                    mesh.normals[tri[1] * 3 + 1],
+                    mesh.normals[tri[1] * 3 + 2],
+                );
+
+                let nc = Vector3::new(
+                    mesh.normals[tri[2] * 3],
+                    mesh.normals[tri[2] * 3 + 1],
+                    mesh.normals[tri[2] * 3 + 2],
+                );
 
-            // We flip the normal automatically in this case
-            if n.dot(dir) > 0.0 {
-                n *= -1.0;
+                // Interpolate
+                let w = 1.0 - ray_hit.hit.u - ray_hit.hit.v;
+                (na * w + nb * ray_hit.hit.u + nc * ray_hit.hit.v).normalize()
+            } else {
+                // As the mesh normal is not provided
+                // we will uses the geometric normals
+                // fortunately, embree computes this information for us
+                Vector3::new(ray_hit.hit.Ng_x, ray_hit.hit.Ng_y, ray_hit.hit.Ng_z).normalize()
             }
+        };
+
+        // We flip the normal automatically in this case
+        if n.dot(dir) > 0.0 {
+            n *= -1.0;
+        }
 
-            // Create local frame
-            let frame = Frame::new(n);
-            let p = self.camera.pos + dir * ray_hit.ray.tfar;
-
-            // Do cosine weighted sampling of the outgoing direction
-            // note that we will not evaluate the cosine term from this point
-            // as it get canceled by the PDF
-            let dir = frame.to_world(cosine_sample_hemisphere(u));
-
-            // Launch a second ray from the intersection point
-            let ray = Ray::new(p, dir);
-            let mut ray_hit = RayHit::new(ray);
-            ray_hit.ray.tnear = 0.00001; // Avoid self intersection
-            let mut intersection_ctx = IntersectContext::incoherent();
-            self.rtscene.intersect(&mut intersection_ctx, &mut ray_hit);
-            if ray_hit.hit.hit() {
-                match self.max_distance {
-                    None => 0.0,
-                    Some(t) => {
-                        if ray_hit.ray.tfar > t {
-                            1.0
-                        } else {
-                            0.0
-                        }
+        // Create local frame
+        let frame = Frame::new(n);
+        let p = self.camera.pos + dir * ray_hit.ray.tfar;
+
+        // Do cosine weighted sampling of the outgoing direction
+        // note that we will not evaluate the cosine term from this point
+        // as it get canceled by the PDF
+        let dir = frame.to_world(cosine_sample_hemisphere(u));
+
+        // Launch a second ray from the intersection point
+        let mut ao_ray = RayHit::new(Ray::new(p, dir));
+        ao_ray.ray.tnear = 0.00001; // Avoid self intersection
+        Some(ao_ray)
+    }
+
+    // Compute the AO from a traced AO ray
+    pub fn occlusion(&self, ao_ray: &RayHit) -> f32 {
+        if ao_ray.hit.hit() {
+            match self.max_distance {
+                None => 0.0,
+                Some(t) => {
+                    if ao_ray.ray.tfar > t {
+                        1.0
+                    } else {
+                        0.0
                     }
                 }
-            } else {
-                1.0
             }
         } else {
-            0.0
+            1.0
         }
     }
 }
@@ -247,7 +254,8 @@ fn main() {
                     mesh.positions[i * 3 + 1],
                     mesh.positions[i * 3 + 2],
                     0.0,
-                );
+                )
+                .into();
             }
 
             for i in 0..mesh.indices.len() / 3 {
@@ -255,12 +263,12 @@ fn main() {
                     mesh.indices[i * 3],
                     mesh.indices[i * 3 + 1],
                     mesh.indices[i * 3 + 2],
-                );
+                )
+                .into();
             }
         }
-        let mut tri_geom = Geometry::Triangle(tris);
-        tri_geom.commit();
-        tri_geoms.push(tri_geom);
+        tris.commit();
+        tri_geoms.push(tris);
     }
     display = display.aabb(aabb);
 
@@ -315,18 +323,31 @@ fn main() {
             }
         }
 
-        // Render the scene with Rayon. Here each pixel compute 1 spp AO
-        img.par_chunks_mut(image.width() as usize)
+        // Render the scene with Rayon. Here each pixel compute 1 spp AO.
+        // The camera rays are traced in parallel a row at a time
+        let width = img_dims.0 as usize;
+        let mut camera_rays = scene.camera_rays(img_dims);
+        scene.rtscene.par_intersect(&mut camera_rays, width);
+
+        // Then an AO ray is traced for each pixel whose camera ray hit the scene
+        let (pixels, mut ao_rays): (Vec<usize>, Vec<RayHit>) = camera_rays
+            .par_iter()
             .enumerate()
-            .for_each(|(y, row)| {
-                let mut rng = rand::thread_rng();
-                for (x, p) in row.iter_mut().enumerate() {
-                    let u = Point2::new(rng.gen(), rng.gen());
-                    // Weighting average
-                    (*p) =
-                        (*p * spp as f32 + scene.render(x as u32, y as u32, u)) / (spp + 1) as f32;
-                }
-            });
+            .filter_map(|(i, ray_hit)| {
+                let u = Point2::new(rand::random(), rand::random());
+                scene.ao_ray(ray_hit, u).map(|ao_ray| (i, ao_ray))
+            })
+            .unzip();
+        scene.rtscene.par_intersect(&mut ao_rays, width);
+
+        let mut ao = vec![0.0; camera_rays.len()];
+        for (i, ao_ray) in pixels.iter().zip(ao_rays.iter()) {
+            ao[*i] = scene.occlusion(ao_ray);
+        }
+        // Weighting average
+        img.par_iter_mut().zip(ao.par_iter()).for_each(|(p, v)| {
+            (*p) = (*p * spp as f32 + v) / (spp + 1) as f32;
+        });
         spp += 1;
 
         // Copy the accumulated result inside the image buffer
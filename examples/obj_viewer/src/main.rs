@@ -8,7 +8,7 @@ extern crate tobj;
 use std::path::Path;
 
 use cgmath::{InnerSpace, Vector3, Vector4};
-use embree::{Device, Geometry, IntersectContext, Ray, RayHit, Scene, TriangleMesh};
+use embree::{Device, EmbreeGeometry, Ray, Scene, TriangleMesh};
 use support::{Camera, AABB};
 
 fn main() {
@@ -44,7 +44,8 @@ fn main() {
                     mesh.positions[i * 3 + 1],
                     mesh.positions[i * 3 + 2],
                     0.0,
-                );
+                )
+                .into();
             }
 
             for i in 0..mesh.indices.len() / 3 {
@@ -52,12 +53,12 @@ fn main() {
                     mesh.indices[i * 3],
                     mesh.indices[i * 3 + 1],
                     mesh.indices[i * 3 + 2],
-                );
+                )
+                .into();
             }
         }
-        let mut tri_geom = Geometry::Triangle(tris);
-        tri_geom.commit();
-        tri_geoms.push(tri_geom);
+        tris.commit();
+        tri_geoms.push(tris);
     }
     display = display.aabb(aabb);
 
@@ -69,8 +70,6 @@ fn main() {
     }
     let rtscene = scene.commit();
 
-    let mut intersection_ctx = IntersectContext::coherent();
-
     display.run(|image, camera_pose, _| {
         for p in image.iter_mut() {
             *p = 0;
@@ -87,14 +86,12 @@ fn main() {
         for j in 0..img_dims.1 {
             for i in 0..img_dims.0 {
                 let dir = camera.ray_dir((i as f32 + 0.5, j as f32 + 0.5));
-                let ray = Ray::new(camera.pos, dir);
-                let mut ray_hit = RayHit::new(ray);
-                rtscene.intersect(&mut intersection_ctx, &mut ray_hit);
-                if ray_hit.hit.hit() {
+                if let Some(hit) = rtscene.intersect_ray(Ray::new(camera.pos, dir)) {
                     let p = image.get_pixel_mut(i, j);
-                    let mesh = &models[mesh_ids[ray_hit.hit.geomID as usize] as usize].mesh;
+                    let mesh = &models[mesh_ids[hit.geom_id as usize] as usize].mesh;
+                    let (u, v) = hit.uv;
                     if !mesh.normals.is_empty() {
-                        let prim = ray_hit.hit.primID as usize;
+                        let prim = hit.prim_id as usize;
                         let tri = [
                             mesh.indices[prim * 3] as usize,
                             mesh.indices[prim * 3 + 1] as usize,
@@ -119,16 +116,16 @@ fn main() {
                             mesh.normals[tri[2] * 3 + 2],
                         );
 
-                        let w = 1.0 - ray_hit.hit.u - ray_hit.hit.v;
-                        let mut n = (na * w + nb * ray_hit.hit.u + nc * ray_hit.hit.v).normalize();
+                        let w = 1.0 - u - v;
+                        let mut n = (na * w + nb * u + nc * v).normalize();
                         n = (n + Vector3::new(1.0, 1.0, 1.0)) * 0.5;
 
                         p[0] = (n.x * 255.0) as u8;
                         p[1] = (n.y * 255.0) as u8;
                         p[2] = (n.z * 255.0) as u8;
                     } else {
-                        p[0] = (ray_hit.hit.u * 255.0) as u8;
-                        p[1] = (ray_hit.hit.v * 255.0) as u8;
+                        p[0] = (u * 255.0) as u8;
+                        p[1] = (v * 255.0) as u8;
                         p[2] = 0;
                     }
                 }
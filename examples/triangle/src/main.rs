@@ -5,7 +5,7 @@ extern crate embree;
 extern crate support;
 
 use cgmath::{Vector3, Vector4};
-use embree::{Device, Geometry, IntersectContext, RayHitN, RayN, Scene, TriangleMesh};
+use embree::{Device, EmbreeGeometry, IntersectContext, RayHitN, RayN, Scene, TriangleMesh};
 
 fn main() {
     let mut display = support::Display::new(512, 512, "triangle");
@@ -16,17 +16,16 @@ fn main() {
     {
         let mut verts = triangle.vertex_buffer.map();
         let mut tris = triangle.index_buffer.map();
-        verts[0] = Vector4::new(-1.0, 0.0, 0.0, 0.0);
-        verts[1] = Vector4::new(0.0, 1.0, 0.0, 0.0);
-        verts[2] = Vector4::new(1.0, 0.0, 0.0, 0.0);
+        verts[0] = Vector4::new(-1.0, 0.0, 0.0, 0.0).into();
+        verts[1] = Vector4::new(0.0, 1.0, 0.0, 0.0).into();
+        verts[2] = Vector4::new(1.0, 0.0, 0.0, 0.0).into();
 
-        tris[0] = Vector3::new(0, 1, 2);
+        tris[0] = Vector3::new(0, 1, 2).into();
     }
-    let mut tri_geom = Geometry::Triangle(triangle);
-    tri_geom.commit();
+    triangle.commit();
 
     let mut scene = Scene::new(&device);
-    scene.attach_geometry(tri_geom);
+    scene.attach_geometry(triangle);
     let rtscene = scene.commit();
 
     let mut intersection_ctx = IntersectContext::coherent();
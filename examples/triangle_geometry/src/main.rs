@@ -5,65 +5,65 @@ extern crate embree;
 extern crate support;
 
 use cgmath::{Vector3, Vector4};
-use embree::{Device, Geometry, IntersectContext, QuadMesh, Ray, RayHit, Scene, TriangleMesh};
+use embree::{
+    Device, EmbreeGeometry, IntersectContext, QuadMesh, Ray, RayHit, Scene, TriangleMesh,
+};
 use support::Camera;
 
-fn make_cube<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_cube<'a>(device: &'a Device) -> TriangleMesh<'a> {
     let mut mesh = TriangleMesh::unanimated(device, 12, 8);
     {
         let mut verts = mesh.vertex_buffer.map();
         let mut tris = mesh.index_buffer.map();
 
-        verts[0] = Vector4::new(-1.0, -1.0, -1.0, 0.0);
-        verts[1] = Vector4::new(-1.0, -1.0, 1.0, 0.0);
-        verts[2] = Vector4::new(-1.0, 1.0, -1.0, 0.0);
-        verts[3] = Vector4::new(-1.0, 1.0, 1.0, 0.0);
-        verts[4] = Vector4::new(1.0, -1.0, -1.0, 0.0);
-        verts[5] = Vector4::new(1.0, -1.0, 1.0, 0.0);
-        verts[6] = Vector4::new(1.0, 1.0, -1.0, 0.0);
-        verts[7] = Vector4::new(1.0, 1.0, 1.0, 0.0);
+        verts[0] = Vector4::new(-1.0, -1.0, -1.0, 0.0).into();
+        verts[1] = Vector4::new(-1.0, -1.0, 1.0, 0.0).into();
+        verts[2] = Vector4::new(-1.0, 1.0, -1.0, 0.0).into();
+        verts[3] = Vector4::new(-1.0, 1.0, 1.0, 0.0).into();
+        verts[4] = Vector4::new(1.0, -1.0, -1.0, 0.0).into();
+        verts[5] = Vector4::new(1.0, -1.0, 1.0, 0.0).into();
+        verts[6] = Vector4::new(1.0, 1.0, -1.0, 0.0).into();
+        verts[7] = Vector4::new(1.0, 1.0, 1.0, 0.0).into();
 
         // left side
-        tris[0] = Vector3::new(0, 2, 1);
-        tris[1] = Vector3::new(1, 2, 3);
+        tris[0] = Vector3::new(0, 2, 1).into();
+        tris[1] = Vector3::new(1, 2, 3).into();
 
         // right side
-        tris[2] = Vector3::new(4, 5, 6);
-        tris[3] = Vector3::new(5, 7, 6);
+        tris[2] = Vector3::new(4, 5, 6).into();
+        tris[3] = Vector3::new(5, 7, 6).into();
 
         // bottom side
-        tris[4] = Vector3::new(0, 1, 4);
-        tris[5] = Vector3::new(1, 5, 4);
+        tris[4] = Vector3::new(0, 1, 4).into();
+        tris[5] = Vector3::new(1, 5, 4).into();
 
         // top side
-        tris[6] = Vector3::new(2, 6, 3);
-        tris[7] = Vector3::new(3, 6, 7);
+        tris[6] = Vector3::new(2, 6, 3).into();
+        tris[7] = Vector3::new(3, 6, 7).into();
 
         // front side
-        tris[8] = Vector3::new(0, 4, 2);
-        tris[9] = Vector3::new(2, 4, 6);
+        tris[8] = Vector3::new(0, 4, 2).into();
+        tris[9] = Vector3::new(2, 4, 6).into();
 
         // back side
-        tris[10] = Vector3::new(1, 3, 5);
-        tris[11] = Vector3::new(3, 7, 5);
+        tris[10] = Vector3::new(1, 3, 5).into();
+        tris[11] = Vector3::new(3, 7, 5).into();
     }
-    let mut mesh = Geometry::Triangle(mesh);
     mesh.commit();
     mesh
 }
-fn make_ground_plane<'a>(device: &'a Device) -> Geometry<'a> {
+fn make_ground_plane<'a>(device: &'a Device) -> QuadMesh<'a> {
     let mut mesh = QuadMesh::unanimated(device, 1, 4);
     {
         let mut verts = mesh.vertex_buffer.map();
         let mut quads = mesh.index_buffer.map();
-        verts[0] = Vector4::new(-10.0, -2.0, -10.0, 0.0);
-        verts[1] = Vector4::new(-10.0, -2.0, 10.0, 0.0);
-        verts[2] = Vector4::new(10.0, -2.0, 10.0, 0.0);
-        verts[3] = Vector4::new(10.0, -2.0, -10.0, 0.0);
+        verts[0] = Vector4::new(-10.0, -2.0, -10.0, 0.0).into();
+        verts[1] = Vector4::new(-10.0, -2.0, 10.0, 0.0).into();
+        verts[2] = Vector4::new(10.0, -2.0, 10.0, 0.0).into();
+        verts[3] = Vector4::new(10.0, -2.0, -10.0, 0.0).into();
 
-        quads[0] = Vector4::new(0, 1, 2, 3);
+        quads[0] = Vector4::new(0, 1, 2, 3).into();
     }
-    let mut mesh = Geometry::Quad(mesh);
     mesh.commit();
     mesh
 }
@@ -0,0 +1,130 @@
+use mint::{Point3, Vector3};
+use std::f32;
+
+use math;
+use sys::RTCBounds;
+
+/// An axis-aligned bounding box, e.g. the bounds of a committed scene.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new<V: Into<Vector3<f32>>>(min: V, max: V) -> Aabb {
+        Aabb {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+    /// An empty box, which contains nothing and is the identity for `union`
+    pub fn empty() -> Aabb {
+        Aabb::new([f32::INFINITY; 3], [f32::NEG_INFINITY; 3])
+    }
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    /// Get the box containing both `self` and `other`
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            [
+                f32::min(self.min.x, other.min.x),
+                f32::min(self.min.y, other.min.y),
+                f32::min(self.min.z, other.min.z),
+            ],
+            [
+                f32::max(self.max.x, other.max.x),
+                f32::max(self.max.y, other.max.y),
+                f32::max(self.max.z, other.max.z),
+            ],
+        )
+    }
+    /// Get the box containing both `self` and the point `p`
+    pub fn union_point<V: Into<Vector3<f32>>>(&self, p: V) -> Aabb {
+        let p = p.into();
+        self.union(&Aabb::new(p, p))
+    }
+    /// Check if the point `p` is inside the box, including its boundary
+    pub fn contains<V: Into<Vector3<f32>>>(&self, p: V) -> bool {
+        let p = p.into();
+        p.x >= self.min.x
+            && p.y >= self.min.y
+            && p.z >= self.min.z
+            && p.x <= self.max.x
+            && p.y <= self.max.y
+            && p.z <= self.max.z
+    }
+    /// Check if `other` is entirely inside the box
+    pub fn contains_aabb(&self, other: &Aabb) -> bool {
+        other.is_empty() || (self.contains(other.min) && self.contains(other.max))
+    }
+    /// The vector from the min to the max corner of the box
+    pub fn diagonal(&self) -> Vector3<f32> {
+        math::sub(self.max.into(), self.min.into()).into()
+    }
+    pub fn center(&self) -> Vector3<f32> {
+        math::scale(math::add(self.min.into(), self.max.into()), 0.5).into()
+    }
+}
+
+impl From<RTCBounds> for Aabb {
+    fn from(b: RTCBounds) -> Aabb {
+        Aabb::new(
+            [b.lower_x, b.lower_y, b.lower_z],
+            [b.upper_x, b.upper_y, b.upper_z],
+        )
+    }
+}
+
+impl From<Aabb> for RTCBounds {
+    fn from(b: Aabb) -> RTCBounds {
+        RTCBounds {
+            lower_x: b.min.x,
+            lower_y: b.min.y,
+            lower_z: b.min.z,
+            align0: 0.0,
+            upper_x: b.max.x,
+            upper_y: b.max.y,
+            upper_z: b.max.z,
+            align1: 0.0,
+        }
+    }
+}
+
+impl From<(Point3<f32>, Point3<f32>)> for Aabb {
+    fn from(b: (Point3<f32>, Point3<f32>)) -> Aabb {
+        Aabb::new([b.0.x, b.0.y, b.0.z], [b.1.x, b.1.y, b.1.z])
+    }
+}
+
+impl From<Aabb> for (Point3<f32>, Point3<f32>) {
+    fn from(b: Aabb) -> (Point3<f32>, Point3<f32>) {
+        (
+            Point3 {
+                x: b.min.x,
+                y: b.min.y,
+                z: b.min.z,
+            },
+            Point3 {
+                x: b.max.x,
+                y: b.max.y,
+                z: b.max.z,
+            },
+        )
+    }
+}
+
+#[test]
+fn test_aabb_union_contains() {
+    let a = Aabb::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+    let b = Aabb::empty().union_point([-1.0, 2.0, 0.5]);
+    let u = a.union(&b);
+    assert_eq!(u.min, Vector3::from([-1.0, 0.0, 0.0]));
+    assert_eq!(u.max, Vector3::from([1.0, 2.0, 1.0]));
+    assert_eq!(u.diagonal(), Vector3::from([2.0, 2.0, 1.0]));
+    assert!(u.contains_aabb(&a) && u.contains_aabb(&b));
+    assert!(!a.contains([-1.0, 2.0, 0.5]));
+    assert!(Aabb::empty().is_empty());
+    assert_eq!(Aabb::empty().union(&a), a);
+}
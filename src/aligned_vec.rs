@@ -0,0 +1,227 @@
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::{cmp, fmt, mem, ptr, slice};
+
+/// A growable array like `Vec` whose buffer is aligned to at least `A` bytes,
+/// e.g. for the SoA ray streams passed to Embree, which must be 16 byte aligned.
+/// `A` must be a power of two.
+pub struct AlignedVec<T, const A: usize = 16> {
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+}
+
+unsafe impl<T: Send, const A: usize> Send for AlignedVec<T, A> {}
+unsafe impl<T: Sync, const A: usize> Sync for AlignedVec<T, A> {}
+
+impl<T, const A: usize> AlignedVec<T, A> {
+    const ALIGN: usize = if mem::align_of::<T>() > A {
+        mem::align_of::<T>()
+    } else {
+        A
+    };
+
+    pub fn new() -> AlignedVec<T, A> {
+        assert!(
+            A.is_power_of_two(),
+            "AlignedVec alignment must be a power of two"
+        );
+        AlignedVec {
+            ptr: Self::dangling(),
+            len: 0,
+            cap: if mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                0
+            },
+        }
+    }
+    pub fn with_capacity(cap: usize) -> AlignedVec<T, A> {
+        let mut v = AlignedVec::new();
+        v.reserve(cap);
+        v
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+    /// Reserve capacity for at least `additional` more elements
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len.checked_add(additional).expect("capacity overflow");
+        if required > self.cap {
+            let cap = cmp::max(required, self.cap * 2);
+            self.grow(cap);
+        }
+    }
+    pub fn push(&mut self, value: T) {
+        if self.len == self.cap {
+            self.reserve(1);
+        }
+        unsafe {
+            ptr::write(self.ptr.as_ptr().add(self.len), value);
+        }
+        self.len += 1;
+    }
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            unsafe { Some(ptr::read(self.ptr.as_ptr().add(self.len))) }
+        }
+    }
+    /// Shorten the vector to `len` elements, dropping the rest. Has no
+    /// effect if the vector is already shorter.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            let tail = ptr::slice_from_raw_parts_mut(
+                unsafe { self.ptr.as_ptr().add(len) },
+                self.len - len,
+            );
+            self.len = len;
+            unsafe {
+                ptr::drop_in_place(tail);
+            }
+        }
+    }
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    fn dangling() -> NonNull<T> {
+        // Even empty buffers must be aligned when passed to Embree
+        unsafe { NonNull::new_unchecked(Self::ALIGN as *mut T) }
+    }
+    fn layout(cap: usize) -> Layout {
+        let size = mem::size_of::<T>()
+            .checked_mul(cap)
+            .expect("capacity overflow");
+        Layout::from_size_align(size, Self::ALIGN).expect("capacity overflow")
+    }
+    fn grow(&mut self, cap: usize) {
+        // Zero sized types never need to allocate, their capacity is usize::MAX
+        assert!(mem::size_of::<T>() != 0, "capacity overflow");
+        let new_layout = Self::layout(cap);
+        let new_ptr = unsafe {
+            if self.cap == 0 {
+                alloc::alloc(new_layout)
+            } else {
+                alloc::realloc(
+                    self.ptr.as_ptr() as *mut u8,
+                    Self::layout(self.cap),
+                    new_layout.size(),
+                )
+            }
+        };
+        self.ptr = match NonNull::new(new_ptr as *mut T) {
+            Some(p) => p,
+            None => alloc::handle_alloc_error(new_layout),
+        };
+        self.cap = cap;
+    }
+}
+
+impl<T: Clone, const A: usize> AlignedVec<T, A> {
+    /// Create a vector of `len` copies of `value`
+    pub fn from_elem(value: T, len: usize) -> AlignedVec<T, A> {
+        let mut v = AlignedVec::with_capacity(len);
+        v.resize(len, value);
+        v
+    }
+    /// Resize the vector to `len` elements, filling new elements with
+    /// copies of `value` or dropping the elements past `len`.
+    pub fn resize(&mut self, len: usize, value: T) {
+        if len > self.len {
+            self.reserve(len - self.len);
+            while self.len < len {
+                self.push(value.clone());
+            }
+        } else {
+            self.truncate(len);
+        }
+    }
+}
+
+impl<T, const A: usize> Drop for AlignedVec<T, A> {
+    fn drop(&mut self) {
+        self.clear();
+        if mem::size_of::<T>() != 0 && self.cap != 0 {
+            unsafe {
+                alloc::dealloc(self.ptr.as_ptr() as *mut u8, Self::layout(self.cap));
+            }
+        }
+    }
+}
+
+impl<T, const A: usize> Deref for AlignedVec<T, A> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T, const A: usize> DerefMut for AlignedVec<T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Clone, const A: usize> Clone for AlignedVec<T, A> {
+    fn clone(&self) -> AlignedVec<T, A> {
+        let mut v = AlignedVec::with_capacity(self.len);
+        for x in self.iter() {
+            v.push(x.clone());
+        }
+        v
+    }
+}
+
+impl<T, const A: usize> Default for AlignedVec<T, A> {
+    fn default() -> AlignedVec<T, A> {
+        AlignedVec::new()
+    }
+}
+
+impl<T: fmt::Debug, const A: usize> fmt::Debug for AlignedVec<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[test]
+fn test_aligned_vec() {
+    let mut v = AlignedVec::<f32, 64>::from_elem(1.0, 24);
+    assert_eq!(v.as_ptr() as usize % 64, 0);
+    assert!(v.iter().all(|x| *x == 1.0));
+    v.resize(100, 2.0);
+    assert_eq!(v.as_ptr() as usize % 64, 0);
+    assert_eq!(v.len(), 100);
+    assert_eq!(v[99], 2.0);
+    v.truncate(10);
+    v.push(3.0);
+    assert_eq!(&v[8..], &[1.0, 1.0, 3.0]);
+    assert_eq!(v.pop(), Some(3.0));
+    v.clear();
+    assert!(v.is_empty());
+    assert_eq!(AlignedVec::<u8>::new().as_ptr() as usize % 16, 0);
+
+    // Elements with destructors are dropped exactly once
+    let rc = ::std::rc::Rc::new(());
+    let mut v = AlignedVec::<_, 16>::from_elem(rc.clone(), 5);
+    v.truncate(2);
+    assert_eq!(::std::rc::Rc::strong_count(&rc), 3);
+    drop(v);
+    assert_eq!(::std::rc::Rc::strong_count(&rc), 1);
+}
@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+use std::os::raw;
+use std::sync::Mutex;
+use std::{mem, ptr, slice};
+
+use aabb::Aabb;
+use device::Device;
+use sys::*;
+use {BuildFlags, BuildQuality, Error};
+
+/// A primitive to build a BVH over, identified by its geometry and primitive ID
+pub type BuildPrimitive = RTCBuildPrimitive;
+
+impl BuildPrimitive {
+    pub fn new(bounds: Aabb, geom_id: u32, prim_id: u32) -> BuildPrimitive {
+        RTCBuildPrimitive {
+            lower_x: bounds.min.x,
+            lower_y: bounds.min.y,
+            lower_z: bounds.min.z,
+            geomID: geom_id,
+            upper_x: bounds.max.x,
+            upper_y: bounds.max.y,
+            upper_z: bounds.max.z,
+            primID: prim_id,
+        }
+    }
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(
+            [self.lower_x, self.lower_y, self.lower_z],
+            [self.upper_x, self.upper_y, self.upper_z],
+        )
+    }
+}
+
+/// A node of a BVH built by `BvhBuilder`, owning its children
+#[derive(Debug)]
+pub enum BvhNode<N, L> {
+    Inner {
+        node: N,
+        /// The bounds of each child
+        bounds: Vec<Aabb>,
+        children: Vec<BvhNode<N, L>>,
+    },
+    Leaf(L),
+}
+
+/// Builder for BVHs over user primitives using Embree's BVH builders,
+/// with user types `N` and `L` stored in the inner nodes and leaves.
+/// The callbacks are called from Embree's build threads, so must be `Sync`.
+pub struct BvhBuilder<'a, N, L> {
+    device: &'a Device,
+    build_quality: BuildQuality,
+    build_flags: BuildFlags,
+    max_branching_factor: u32,
+    max_depth: u32,
+    sah_block_size: u32,
+    min_leaf_size: u32,
+    max_leaf_size: u32,
+    traversal_cost: f32,
+    intersection_cost: f32,
+    create_node: CreateNodeFn<'a, N>,
+    create_leaf: CreateLeafFn<'a, L>,
+    set_node_bounds: Option<SetNodeBoundsFn<'a, N>>,
+    set_node_children: Option<SetNodeChildrenFn<'a, N, L>>,
+    split_primitive: Option<SplitPrimitiveFn<'a>>,
+}
+
+type CreateNodeFn<'a, N> = Box<dyn Fn(usize) -> N + Send + Sync + 'a>;
+type CreateLeafFn<'a, L> = Box<dyn Fn(&[BuildPrimitive]) -> L + Send + Sync + 'a>;
+type SetNodeBoundsFn<'a, N> = Box<dyn Fn(&mut N, &[Aabb]) + Send + Sync + 'a>;
+type SetNodeChildrenFn<'a, N, L> = Box<dyn Fn(&mut N, &[BvhNode<N, L>]) + Send + Sync + 'a>;
+type SplitPrimitiveFn<'a> =
+    Box<dyn Fn(&BuildPrimitive, u32, f32) -> (Aabb, Aabb) + Send + Sync + 'a>;
+
+impl<'a, N: Send, L: Send> BvhBuilder<'a, N, L> {
+    /// Create a builder with Embree's default build arguments. `create_node`
+    /// is passed the number of children of the inner node being created and
+    /// `create_leaf` the primitives in the leaf being created.
+    pub fn new<CN, CL>(device: &'a Device, create_node: CN, create_leaf: CL) -> BvhBuilder<'a, N, L>
+    where
+        CN: Fn(usize) -> N + Send + Sync + 'a,
+        CL: Fn(&[BuildPrimitive]) -> L + Send + Sync + 'a,
+    {
+        BvhBuilder {
+            device: device,
+            build_quality: BuildQuality::MEDIUM,
+            build_flags: BuildFlags::NONE,
+            max_branching_factor: 2,
+            max_depth: 32,
+            sah_block_size: 1,
+            min_leaf_size: 1,
+            max_leaf_size: RTCBuildConstants_RTC_BUILD_MAX_PRIMITIVES_PER_LEAF,
+            traversal_cost: 1.0,
+            intersection_cost: 1.0,
+            create_node: Box::new(create_node),
+            create_leaf: Box::new(create_leaf),
+            set_node_bounds: None,
+            set_node_children: None,
+            split_primitive: None,
+        }
+    }
+    /// Set the quality of the build. `HIGH` quality enables spatial splits,
+    /// which requires a `split_primitive` callback.
+    pub fn build_quality(mut self, quality: BuildQuality) -> BvhBuilder<'a, N, L> {
+        self.build_quality = quality;
+        self
+    }
+    pub fn build_flags(mut self, flags: BuildFlags) -> BvhBuilder<'a, N, L> {
+        self.build_flags = flags;
+        self
+    }
+    /// Set the maximum number of children of the inner nodes, at most 8
+    pub fn max_branching_factor(mut self, factor: u32) -> BvhBuilder<'a, N, L> {
+        self.max_branching_factor = factor;
+        self
+    }
+    pub fn max_depth(mut self, depth: u32) -> BvhBuilder<'a, N, L> {
+        self.max_depth = depth;
+        self
+    }
+    /// Set the block size the SAH is computed for, i.e. the number of
+    /// primitives intersected together in a leaf.
+    pub fn sah_block_size(mut self, size: u32) -> BvhBuilder<'a, N, L> {
+        self.sah_block_size = size;
+        self
+    }
+    pub fn min_leaf_size(mut self, size: u32) -> BvhBuilder<'a, N, L> {
+        self.min_leaf_size = size;
+        self
+    }
+    /// Set the maximum number of primitives in a leaf, at most 32
+    pub fn max_leaf_size(mut self, size: u32) -> BvhBuilder<'a, N, L> {
+        self.max_leaf_size = size;
+        self
+    }
+    /// Set the estimated cost of traversing an inner node, used by the SAH
+    pub fn traversal_cost(mut self, cost: f32) -> BvhBuilder<'a, N, L> {
+        self.traversal_cost = cost;
+        self
+    }
+    /// Set the estimated cost of intersecting a primitive, used by the SAH
+    pub fn intersection_cost(mut self, cost: f32) -> BvhBuilder<'a, N, L> {
+        self.intersection_cost = cost;
+        self
+    }
+    /// Set a callback called with the bounds of the children of each inner
+    /// node after it's created. The bounds are also stored in the `BvhNode`.
+    pub fn set_node_bounds<F>(mut self, f: F) -> BvhBuilder<'a, N, L>
+    where
+        F: Fn(&mut N, &[Aabb]) + Send + Sync + 'a,
+    {
+        self.set_node_bounds = Some(Box::new(f));
+        self
+    }
+    /// Set a callback called with the children of each inner node once the
+    /// subtrees below it are built, e.g. to compute data for the node from
+    /// its children.
+    pub fn set_node_children<F>(mut self, f: F) -> BvhBuilder<'a, N, L>
+    where
+        F: Fn(&mut N, &[BvhNode<N, L>]) + Send + Sync + 'a,
+    {
+        self.set_node_children = Some(Box::new(f));
+        self
+    }
+    /// Set the callback used to split a primitive at `position` along the
+    /// axis `dimension` for spatial splits, which returns the bounds of the
+    /// parts of the primitive to the left and right of the split.
+    pub fn split_primitive<F>(mut self, f: F) -> BvhBuilder<'a, N, L>
+    where
+        F: Fn(&BuildPrimitive, u32, f32) -> (Aabb, Aabb) + Send + Sync + 'a,
+    {
+        self.split_primitive = Some(Box::new(f));
+        self
+    }
+    /// Build a BVH over the primitives. The builder reorders the primitives
+    /// and, when making spatial splits, writes the split primitives to the
+    /// spare capacity of `primitives`, so extra capacity should be reserved
+    /// for `HIGH` quality builds. Embree doesn't report how many primitives
+    /// there are after the splits, so the length of `primitives` is left
+    /// unchanged and the split primitives are only passed to `create_leaf`.
+    pub fn build(&self, primitives: &mut Vec<BuildPrimitive>) -> Result<BvhNode<N, L>, Error> {
+        if primitives.is_empty() {
+            return Ok(BvhNode::Leaf((self.create_leaf)(&[])));
+        }
+        let state = BuildState {
+            builder: self,
+            nodes: Mutex::new(HashSet::new()),
+        };
+        unsafe {
+            let bvh = rtcNewBVH(self.device.handle);
+            let args = RTCBuildArguments {
+                byteSize: mem::size_of::<RTCBuildArguments>(),
+                buildQuality: self.build_quality,
+                buildFlags: self.build_flags,
+                maxBranchingFactor: self.max_branching_factor,
+                maxDepth: self.max_depth,
+                sahBlockSize: self.sah_block_size,
+                minLeafSize: self.min_leaf_size,
+                maxLeafSize: self.max_leaf_size,
+                traversalCost: self.traversal_cost,
+                intersectionCost: self.intersection_cost,
+                bvh: bvh,
+                primitives: primitives.as_mut_ptr(),
+                primitiveCount: primitives.len(),
+                primitiveArrayCapacity: primitives.capacity(),
+                createNode: Some(create_node::<N, L>),
+                setNodeChildren: Some(set_node_children::<N, L>),
+                setNodeBounds: Some(set_node_bounds::<N, L>),
+                createLeaf: Some(create_leaf::<N, L>),
+                splitPrimitive: match self.split_primitive {
+                    Some(_) => Some(split_primitive::<N, L>),
+                    None => None,
+                },
+                buildProgress: None,
+                userPtr: &state as *const BuildState<N, L> as *mut raw::c_void,
+            };
+            rtcGetDeviceError(self.device.handle);
+            let root = rtcBuildBVH(&args as *const RTCBuildArguments) as *mut BvhNode<N, L>;
+            rtcReleaseBVH(bvh);
+            // Free the nodes which weren't made children of another node,
+            // i.e. all of them if the build failed and only the root otherwise
+            let nodes = state.nodes.into_inner().unwrap();
+            for node in nodes.into_iter().filter(|n| *n != root as usize) {
+                drop(Box::from_raw(node as *mut BvhNode<N, L>));
+            }
+            if root.is_null() {
+                Err(self.device.get_error())
+            } else {
+                Ok(*Box::from_raw(root))
+            }
+        }
+    }
+}
+
+// The nodes are allocated as boxed `BvhNode`s instead of through Embree's
+// thread local allocator, so the tree can outlive the `RTCBVH` and be
+// owned by the caller.
+
+/// The state of a build passed to the callbacks, tracking the nodes
+/// allocated which haven't been moved into their parent yet so they
+/// aren't leaked if the build fails.
+struct BuildState<'b, 'a: 'b, N: 'b, L: 'b> {
+    builder: &'b BvhBuilder<'a, N, L>,
+    nodes: Mutex<HashSet<usize>>,
+}
+
+impl<'b, 'a, N, L> BuildState<'b, 'a, N, L> {
+    fn alloc(&self, node: BvhNode<N, L>) -> *mut raw::c_void {
+        let ptr = Box::into_raw(Box::new(node));
+        self.nodes.lock().unwrap().insert(ptr as usize);
+        ptr as *mut raw::c_void
+    }
+    unsafe fn take(&self, ptr: *mut raw::c_void) -> BvhNode<N, L> {
+        self.nodes.lock().unwrap().remove(&(ptr as usize));
+        *Box::from_raw(ptr as *mut BvhNode<N, L>)
+    }
+}
+
+unsafe extern "C" fn create_node<N, L>(
+    _: RTCThreadLocalAllocator,
+    child_count: raw::c_uint,
+    user_ptr: *mut raw::c_void,
+) -> *mut raw::c_void {
+    let state = &*(user_ptr as *const BuildState<N, L>);
+    let node = BvhNode::<N, L>::Inner {
+        node: (state.builder.create_node)(child_count as usize),
+        bounds: Vec::with_capacity(child_count as usize),
+        children: Vec::with_capacity(child_count as usize),
+    };
+    state.alloc(node)
+}
+
+unsafe extern "C" fn set_node_bounds<N, L>(
+    node_ptr: *mut raw::c_void,
+    child_bounds: *mut *const RTCBounds,
+    child_count: raw::c_uint,
+    user_ptr: *mut raw::c_void,
+) {
+    let builder = (*(user_ptr as *const BuildState<N, L>)).builder;
+    if let BvhNode::Inner {
+        ref mut node,
+        ref mut bounds,
+        ..
+    } = *(node_ptr as *mut BvhNode<N, L>)
+    {
+        let child_bounds = slice::from_raw_parts(child_bounds, child_count as usize);
+        bounds.clear();
+        bounds.extend(child_bounds.iter().map(|b| Aabb::from(**b)));
+        if let Some(ref f) = builder.set_node_bounds {
+            f(node, bounds);
+        }
+    }
+}
+
+unsafe extern "C" fn set_node_children<N, L>(
+    node_ptr: *mut raw::c_void,
+    child_ptrs: *mut *mut raw::c_void,
+    child_count: raw::c_uint,
+    user_ptr: *mut raw::c_void,
+) {
+    let state = &*(user_ptr as *const BuildState<N, L>);
+    if let BvhNode::Inner {
+        ref mut node,
+        ref mut children,
+        ..
+    } = *(node_ptr as *mut BvhNode<N, L>)
+    {
+        let child_ptrs = slice::from_raw_parts(child_ptrs, child_count as usize);
+        children.clear();
+        children.extend(child_ptrs.iter().map(|c| state.take(*c)));
+        if let Some(ref f) = state.builder.set_node_children {
+            f(node, children);
+        }
+    }
+}
+
+unsafe extern "C" fn create_leaf<N, L>(
+    _: RTCThreadLocalAllocator,
+    primitives: *const RTCBuildPrimitive,
+    primitive_count: size_t,
+    user_ptr: *mut raw::c_void,
+) -> *mut raw::c_void {
+    let state = &*(user_ptr as *const BuildState<N, L>);
+    let primitives = if primitive_count > 0 {
+        slice::from_raw_parts(primitives, primitive_count)
+    } else {
+        &[]
+    };
+    let leaf = BvhNode::<N, L>::Leaf((state.builder.create_leaf)(primitives));
+    state.alloc(leaf)
+}
+
+unsafe extern "C" fn split_primitive<N, L>(
+    primitive: *const RTCBuildPrimitive,
+    dimension: raw::c_uint,
+    position: f32,
+    left_bounds: *mut RTCBounds,
+    right_bounds: *mut RTCBounds,
+    user_ptr: *mut raw::c_void,
+) {
+    let builder = (*(user_ptr as *const BuildState<N, L>)).builder;
+    if let Some(ref f) = builder.split_primitive {
+        let (left, right) = f(&*primitive, dimension, position);
+        ptr::write(left_bounds, left.into());
+        ptr::write(right_bounds, right.into());
+    }
+}
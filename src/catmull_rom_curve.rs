@@ -1,8 +1,8 @@
-use cgmath::{Vector2, Vector3, Vector4};
+use mint::{Vector3, Vector4};
 
 use buffer::Buffer;
 use device::Device;
-use geometry::Geometry;
+use geometry::{self, EmbreeGeometry};
 use sys::*;
 use {BufferType, CurveType, Format, GeometryType};
 
@@ -134,4 +134,21 @@ impl<'a> CatmullRomCurve<'a> {
     }
 }
 
+unsafe impl<'a> EmbreeGeometry<'a> for CatmullRomCurve<'a> {
+    type Static = CatmullRomCurve<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for CatmullRomCurve<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
 unsafe impl<'a> Sync for CatmullRomCurve<'a> {}
@@ -0,0 +1,222 @@
+use mint;
+use std::os::raw;
+use std::{mem, ptr};
+
+use aabb::Aabb;
+use device::Device;
+use geometry::{self, EmbreeGeometry, GeometryData};
+use math::{self, Vec3};
+use sys::*;
+use triangle_mesh::TriangleMesh;
+use GeometryType;
+
+/// A pair of primitives found by `CommittedScene::collide` whose bounds
+/// overlap. `geomID0`/`primID0` identify the primitive in the scene `collide`
+/// was called on and `geomID1`/`primID1` the one in the other scene.
+pub type Collision = RTCCollision;
+
+/// A user geometry bounding each triangle of a `TriangleMesh`, for building
+/// the scenes passed to `CommittedScene::collide`, as Embree can only collide
+/// scenes made of user geometry. The primitive IDs of its collisions are the
+/// IDs of the mesh's triangles.
+///
+/// Rays never hit a `CollisionMesh`, so a scene rendering the same mesh
+/// should attach a `TriangleMesh` of it.
+pub struct CollisionMesh<'a> {
+    handle: RTCGeometry,
+    mesh: TriangleMesh<'a>,
+}
+
+impl<'a> CollisionMesh<'a> {
+    pub fn new(device: &'a Device, mesh: TriangleMesh<'a>) -> CollisionMesh<'a> {
+        let h = unsafe { rtcNewGeometry(device.handle, GeometryType::USER) };
+        unsafe {
+            let data = geometry::geometry_data_mut(h);
+            data.geometry_type = Some(GeometryType::USER);
+            data.collision_mesh = mesh.handle;
+            rtcSetGeometryUserPrimitiveCount(h, mesh.num_tris as u32);
+            rtcSetGeometryBoundsFunction(
+                h,
+                Some(bounds_fn),
+                data as *mut GeometryData as *mut raw::c_void,
+            );
+            rtcSetGeometryIntersectFunction(h, Some(intersect_fn));
+            rtcSetGeometryOccludedFunction(h, Some(occluded_fn));
+        }
+        CollisionMesh {
+            handle: h,
+            mesh: mesh,
+        }
+    }
+    pub fn mesh(&self) -> &TriangleMesh<'a> {
+        &self.mesh
+    }
+    /// Get the mesh to update its vertices, after which the `CollisionMesh`
+    /// must be committed again to update the bounds of its triangles.
+    pub fn mesh_mut(&mut self) -> &mut TriangleMesh<'a> {
+        &mut self.mesh
+    }
+    pub fn into_mesh(self) -> TriangleMesh<'a> {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+            let mesh = ptr::read(&self.mesh);
+            mem::forget(self);
+            mesh
+        }
+    }
+}
+
+unsafe impl<'a> EmbreeGeometry<'a> for CollisionMesh<'a> {
+    type Static = CollisionMesh<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for CollisionMesh<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+unsafe impl<'a> Sync for CollisionMesh<'a> {}
+
+unsafe extern "C" fn bounds_fn(args: *const RTCBoundsFunctionArguments) {
+    let args = &*args;
+    let data = &*(args.geometryUserPtr as *const GeometryData);
+    if let Some(tri) = mesh_triangle(data.collision_mesh, args.primID) {
+        let bounds = Aabb::empty()
+            .union_point(tri[0])
+            .union_point(tri[1])
+            .union_point(tri[2]);
+        *args.bounds_o = bounds.into();
+    }
+}
+
+/// A `CollisionMesh` is only used for collisions, so rays pass through it
+unsafe extern "C" fn intersect_fn(_: *const RTCIntersectFunctionNArguments) {}
+
+unsafe extern "C" fn occluded_fn(_: *const RTCOccludedFunctionNArguments) {}
+
+/// Check if the triangles `a` and `b` intersect, including if they only touch,
+/// using the separating axis test. Coplanar triangles are supported.
+pub fn triangles_intersect(a: &[mint::Vector3<f32>; 3], b: &[mint::Vector3<f32>; 3]) -> bool {
+    separating_axis_test(
+        &[a[0].into(), a[1].into(), a[2].into()],
+        &[b[0].into(), b[1].into(), b[2].into()],
+    )
+}
+
+pub(crate) fn separating_axis_test(a: &[Vec3; 3], b: &[Vec3; 3]) -> bool {
+    let edges_a = [
+        math::sub(a[1], a[0]),
+        math::sub(a[2], a[1]),
+        math::sub(a[0], a[2]),
+    ];
+    let edges_b = [
+        math::sub(b[1], b[0]),
+        math::sub(b[2], b[1]),
+        math::sub(b[0], b[2]),
+    ];
+    let normal_a = math::cross(edges_a[0], edges_a[1]);
+    let normal_b = math::cross(edges_b[0], edges_b[1]);
+
+    let separated = |axis: Vec3| {
+        let (min_a, max_a) = project(a, axis);
+        let (min_b, max_b) = project(b, axis);
+        max_a < min_b || max_b < min_a
+    };
+    if separated(normal_a) || separated(normal_b) {
+        return false;
+    }
+    for ea in edges_a.iter() {
+        for eb in edges_b.iter() {
+            if separated(math::cross(*ea, *eb)) {
+                return false;
+            }
+        }
+    }
+    // The edge-edge axes vanish for coplanar triangles, so also test the
+    // in-plane edge normals of each triangle
+    for ea in edges_a.iter() {
+        if separated(math::cross(normal_a, *ea)) {
+            return false;
+        }
+    }
+    for eb in edges_b.iter() {
+        if separated(math::cross(normal_b, *eb)) {
+            return false;
+        }
+    }
+    true
+}
+
+fn project(tri: &[Vec3; 3], axis: Vec3) -> (f32, f32) {
+    let d = [
+        math::dot(tri[0], axis),
+        math::dot(tri[1], axis),
+        math::dot(tri[2], axis),
+    ];
+    (d[0].min(d[1]).min(d[2]), d[0].max(d[1]).max(d[2]))
+}
+
+/// Get the vertices of triangle `prim_id` of the geometry, if it's a
+/// `TriangleMesh` or the `CollisionMesh` of one
+pub(crate) unsafe fn mesh_triangle(geom: RTCGeometry, prim_id: u32) -> Option<[Vec3; 3]> {
+    let data = geometry::geometry_data(geom)?;
+    match data.geometry_type? {
+        GeometryType::USER if !data.collision_mesh.is_null() => {
+            mesh_triangle(data.collision_mesh, prim_id)
+        }
+        GeometryType::TRIANGLE => {
+            let tri = geometry::mesh_primitive::<[u32; 3]>(geom, prim_id as usize);
+            Some([
+                geometry::mesh_vertex(geom, tri[0]),
+                geometry::mesh_vertex(geom, tri[1]),
+                geometry::mesh_vertex(geom, tri[2]),
+            ])
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_triangles_intersect() {
+    let a: [mint::Vector3<f32>; 3] = [
+        [0.0, 0.0, 0.0].into(),
+        [1.0, 0.0, 0.0].into(),
+        [0.0, 1.0, 0.0].into(),
+    ];
+    // Piercing a through its interior
+    let b: [mint::Vector3<f32>; 3] = [
+        [0.25, 0.25, -1.0].into(),
+        [0.25, 0.25, 1.0].into(),
+        [0.5, -1.0, 0.0].into(),
+    ];
+    assert!(triangles_intersect(&a, &b));
+    // Overlapping bounds, but above the hypotenuse of a
+    let c: [mint::Vector3<f32>; 3] = [
+        [0.75, 0.75, -1.0].into(),
+        [0.75, 0.75, 1.0].into(),
+        [1.0, 0.5, 0.0].into(),
+    ];
+    assert!(!triangles_intersect(&a, &c));
+    // Coplanar, overlapping and disjoint
+    let d: [mint::Vector3<f32>; 3] = [
+        [0.25, 0.25, 0.0].into(),
+        [2.0, 0.25, 0.0].into(),
+        [0.25, 2.0, 0.0].into(),
+    ];
+    assert!(triangles_intersect(&a, &d));
+    let e: [mint::Vector3<f32>; 3] = [
+        [0.75, 0.75, 0.0].into(),
+        [2.0, 0.75, 0.0].into(),
+        [0.75, 2.0, 0.0].into(),
+    ];
+    assert!(!triangles_intersect(&a, &e));
+}
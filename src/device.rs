@@ -4,6 +4,7 @@ use std::ffi::CString;
 use std::ptr;
 
 use sys::*;
+use {DeviceProperty, Error};
 
 pub struct Device {
     pub(crate) handle: RTCDevice,
@@ -29,6 +30,46 @@ impl Device {
             handle: unsafe { rtcNewDevice(cfg.as_ptr()) },
         }
     }
+    /// Query a property of the device, e.g. the Embree version or
+    /// which features it was built with.
+    pub fn get_property(&self, prop: DeviceProperty) -> isize {
+        unsafe { rtcGetDeviceProperty(self.handle, prop) as isize }
+    }
+    /// Set a property of the device. Only a few properties can be set,
+    /// see the Embree documentation of `rtcSetDeviceProperty`.
+    pub fn set_property(&mut self, prop: DeviceProperty, value: isize) {
+        unsafe {
+            rtcSetDeviceProperty(self.handle, prop, value as ssize_t);
+        }
+    }
+    /// Check if `Scene::join_commit` is supported, which depends on the
+    /// tasking system Embree was built with.
+    pub fn join_commit_supported(&self) -> bool {
+        self.get_property(DeviceProperty::JOIN_COMMIT_SUPPORTED) != 0
+    }
+    /// Check if the packet width `width` is natively supported by the CPU
+    /// and Embree build, e.g. to pick the `N` of `CommittedScene::intersect_packet`.
+    /// Non-native widths are still supported, but emulated with narrower packets.
+    pub fn native_packet_supported(&self, width: usize) -> bool {
+        let prop = match width {
+            4 => DeviceProperty::NATIVE_RAY4_SUPPORTED,
+            8 => DeviceProperty::NATIVE_RAY8_SUPPORTED,
+            16 => DeviceProperty::NATIVE_RAY16_SUPPORTED,
+            _ => return false,
+        };
+        self.get_property(prop) != 0
+    }
+    /// Check if Embree was built with backface culling, in which case hits on
+    /// the back faces of triangles and quads are always ignored, see
+    /// `EmbreeGeometry::set_backface_culling` for a fallback when it's not.
+    pub fn backface_culling_enabled(&self) -> bool {
+        self.get_property(DeviceProperty::BACKFACE_CULLING_ENABLED) != 0
+    }
+    /// Get the first error which occurred on the device since the last call
+    /// to `get_error`, clearing it. Returns `Error::NONE` if there was no error.
+    pub fn get_error(&self) -> Error {
+        unsafe { rtcGetDeviceError(self.handle) }
+    }
     // TODO: Setup the flush zero and denormals mode needed by Embree
     // using the Rust SIMD when it's in core
 }
@@ -1,58 +1,246 @@
+use std::any::Any;
+use std::os::raw;
+use std::ptr;
+
+use intersect_context::FilterArgs;
+use math::Vec3;
 use sys::*;
+use {BufferType, BuildQuality, GeometryType};
+
+/// Data owned by the crate which is attached to a geometry through its
+/// Embree user pointer, allowing it to be found from the geometry ID in
+/// callbacks and hit records.
+pub(crate) struct GeometryData {
+    /// The type of the geometry, set by geometry types whose buffers
+    /// the crate reads back for point queries
+    pub(crate) geometry_type: Option<GeometryType>,
+    /// The scene instanced by the geometry, or null if it's not an instance
+    pub(crate) instanced_scene: RTCScene,
+    /// The triangle mesh bounded by the geometry, or null if it's not a
+    /// `CollisionMesh`
+    pub(crate) collision_mesh: RTCGeometry,
+    user_data: Option<Box<dyn Any + Send + Sync>>,
+}
 
-use bezier_curve;
-use bspline_curve;
-use catmull_rom_curve;
-use hermite_curve;
-use instance;
-use linear_curve;
-use quad_mesh;
-use triangle_mesh;
-
-pub enum Geometry<'a> {
-    Triangle(triangle_mesh::TriangleMesh<'a>),
-    Quad(quad_mesh::QuadMesh<'a>),
-    Instance(instance::Instance<'a>),
-    LinearCurve(linear_curve::LinearCurve<'a>),
-    BsplineCurve(bspline_curve::BsplineCurve<'a>),
-    BezierCurve(bezier_curve::BezierCurve<'a>),
-    HermiteCurve(hermite_curve::HermiteCurve<'a>),
-    CatmullRomCurve(catmull_rom_curve::CatmullRomCurve<'a>),
-}
-
-/// Geometry trait implemented by all Embree Geometry types
-impl<'a> Geometry<'a> {
-    pub fn handle(&self) -> RTCGeometry {
-        match self {
-            &Geometry::Triangle(ref m) => m.handle,
-            &Geometry::Quad(ref q) => q.handle,
-            &Geometry::Instance(ref i) => i.handle,
-            &Geometry::LinearCurve(ref lc) => lc.handle,
-            &Geometry::BsplineCurve(ref bsc) => bsc.handle,
-            &Geometry::BezierCurve(ref bzc) => bzc.handle,
-            &Geometry::HermiteCurve(ref hc) => hc.handle,
-            &Geometry::CatmullRomCurve(ref crc) => crc.handle,
-        }
-    }
-    pub fn commit(&mut self) {
+/// Geometry trait implemented by all Embree Geometry types, exposing the
+/// per-geometry state which Embree tracks independently of the geometry type.
+/// Changes made through these methods take effect when the geometry is
+/// committed and the scene it's attached to is committed again.
+///
+/// The trait can be implemented outside the crate to wrap geometry types not
+/// provided here and attach them to a `Scene`. The implementing type should own
+/// its `RTCGeometry` handle and release it when dropped.
+///
+/// # Safety
+/// `handle` must return a valid geometry created on the device borrowed for
+/// `'a`, which stays alive as long as `self` does. The implementing type may
+/// have no lifetime parameter other than `'a`, e.g. `Foo<'a>` but not
+/// `Foo<'a, 'b>`, and `Static` must be `Self` with `'a` replaced by `'static`.
+/// It's used to identify the concrete type when downcasting geometry owned by
+/// a `Scene`, which would otherwise let `get_geometry` change a lifetime the
+/// `Scene` doesn't track.
+/// The geometry user pointer is managed by the user data methods below and
+/// must not be set through the handle directly.
+pub unsafe trait EmbreeGeometry<'a>: 'a {
+    /// `Self` with its lifetimes erased, e.g. `TriangleMesh<'static>`
+    type Static: 'static
+    where
+        Self: Sized;
+
+    /// Get the underlying handle to the geometry, e.g. for passing it to
+    /// native code or ISPC kernels.
+    fn handle(&self) -> RTCGeometry;
+
+    /// Commit changes to the geometry so they're picked up the next time
+    /// the scene it's attached to is committed.
+    fn commit(&mut self) {
         unsafe {
             rtcCommitGeometry(self.handle());
         }
     }
+    /// Mark a buffer of the geometry as modified so the changes are picked up
+    /// the next time the geometry is committed. Buffers mapped through
+    /// `Buffer::map` are marked as modified automatically when unmapped.
+    fn update_buffer(&mut self, buf_type: BufferType, slot: u32) {
+        unsafe {
+            rtcUpdateGeometryBuffer(self.handle(), buf_type, slot);
+        }
+    }
+    /// Set the ray mask of the geometry. A ray will only hit the geometry
+    /// if the bitwise and of the ray mask and geometry mask is non-zero.
+    /// Ray masks must be enabled in the Embree build (`EMBREE_RAY_MASK`)
+    /// for the mask to have any effect.
+    fn set_mask(&mut self, mask: u32) {
+        unsafe {
+            rtcSetGeometryMask(self.handle(), mask);
+        }
+    }
+    /// Enable the geometry so that it's considered for ray queries
+    /// the next time the scene it's attached to is committed. Geometries
+    /// are enabled by default.
+    fn enable(&mut self) {
+        unsafe {
+            rtcEnableGeometry(self.handle());
+        }
+    }
+    /// Disable the geometry so that it's ignored by ray queries the next
+    /// time the scene it's attached to is committed, without having to
+    /// detach it and rebuild the scene.
+    fn disable(&mut self) {
+        unsafe {
+            rtcDisableGeometry(self.handle());
+        }
+    }
+    /// Set the quality of the BVH built for the geometry. `REFIT` is only
+    /// supported for triangle and quad meshes and is useful for deforming
+    /// meshes, where the topology stays the same between commits.
+    fn set_build_quality(&mut self, quality: BuildQuality) {
+        unsafe {
+            rtcSetGeometryBuildQuality(self.handle(), quality);
+        }
+    }
+    /// Set the maximum radius scale of curve geometries with per-vertex
+    /// radii, allowing Embree to reduce the number of primitives traversed
+    /// when the curves are far away. Ignored by other geometry types.
+    fn set_max_radius_scale(&mut self, scale: f32) {
+        unsafe {
+            rtcSetGeometryMaxRadiusScale(self.handle(), scale);
+        }
+    }
+    /// Ignore hits on the back faces of the geometry, i.e. where its geometric
+    /// normal points along the ray, using filter functions. This is a fallback
+    /// for Embree builds without backface culling (see
+    /// `Device::backface_culling_enabled`), and is slower than Embree's culling
+    /// since back faces are still intersected. Replaces any filter functions
+    /// set on the geometry through its handle. Only meaningful for triangle
+    /// and quad meshes, as instances can't have filter functions and the
+    /// geometric normal of curves doesn't give their facing.
+    fn set_backface_culling(&mut self, cull: bool) {
+        let filter: RTCFilterFunctionN = if cull { Some(backface_filter) } else { None };
+        unsafe {
+            rtcSetGeometryIntersectFilterFunction(self.handle(), filter);
+            rtcSetGeometryOccludedFilterFunction(self.handle(), filter);
+        }
+    }
+    /// Attach an owned user payload to the geometry, replacing the existing
+    /// one if any. The payload can be looked up from hit records through
+    /// `CommittedScene::user_data`, e.g. to find the material of the hit geometry.
+    fn set_user_data<T: Any + Send + Sync>(&mut self, data: T)
+    where
+        Self: Sized,
+    {
+        unsafe {
+            geometry_data_mut(self.handle()).user_data = Some(Box::new(data));
+        }
+    }
+    /// Attach an owned user payload to the geometry, see `set_user_data`.
+    fn with_user_data<T: Any + Send + Sync>(mut self, data: T) -> Self
+    where
+        Self: Sized,
+    {
+        self.set_user_data(data);
+        self
+    }
+    /// Get the user payload attached to the geometry. Returns `None` if
+    /// there's no user data or it isn't a `T`.
+    fn user_data<T: Any>(&self) -> Option<&T>
+    where
+        Self: Sized,
+    {
+        unsafe { user_data(self.handle()) }
+    }
 }
 
-impl<'a> Drop for Geometry<'a> {
-    fn drop(&mut self) {
-        unsafe {
-            rtcReleaseGeometry(self.handle());
+/// Filter function rejecting hits on back faces, matching Embree's culling
+/// which only accepts hits where the geometric normal faces the ray.
+unsafe extern "C" fn backface_filter(args: *const RTCFilterFunctionNArguments) {
+    let mut args = FilterArgs::new(&*args);
+    for i in 0..args.len() {
+        if args.is_valid(i) {
+            let ray = args.ray(i);
+            let hit = args.hit(i);
+            if ray.dir_x * hit.Ng_x + ray.dir_y * hit.Ng_y + ray.dir_z * hit.Ng_z >= 0.0 {
+                args.reject(i);
+            }
         }
     }
 }
 
-impl<'a> PartialEq<Geometry<'a>> for Geometry<'a> {
-    fn eq(&self, other: &Geometry) -> bool {
-        self.handle() == other.handle()
+/// Get the crate data attached to the geometry, if any
+pub(crate) unsafe fn geometry_data<'b>(handle: RTCGeometry) -> Option<&'b GeometryData> {
+    (rtcGetGeometryUserData(handle) as *const GeometryData).as_ref()
+}
+
+/// Get the crate data attached to the geometry, attaching it if there's none yet
+pub(crate) unsafe fn geometry_data_mut<'b>(handle: RTCGeometry) -> &'b mut GeometryData {
+    let mut data = rtcGetGeometryUserData(handle) as *mut GeometryData;
+    if data.is_null() {
+        data = Box::into_raw(Box::new(GeometryData {
+            geometry_type: None,
+            instanced_scene: ptr::null_mut(),
+            collision_mesh: ptr::null_mut(),
+            user_data: None,
+        }));
+        rtcSetGeometryUserData(handle, data as *mut raw::c_void);
     }
+    &mut *data
 }
 
-impl<'a> Eq for Geometry<'a> {}
+/// Get the user payload attached to the geometry, if it's a `T`.
+pub(crate) unsafe fn user_data<'b, T: Any>(handle: RTCGeometry) -> Option<&'b T> {
+    geometry_data(handle)
+        .and_then(|d| d.user_data.as_ref())
+        .and_then(|d| d.downcast_ref::<T>())
+}
+
+/// Release the user payload attached to the geometry through
+/// `EmbreeGeometry::set_user_data`. Geometry types implemented outside
+/// the crate should call this when dropped, before releasing their
+/// handle, to not leak the payload.
+///
+/// # Safety
+/// `handle` must be a valid geometry, whose user pointer is either null
+/// or was set by `EmbreeGeometry::set_user_data`.
+pub unsafe fn release_user_data(handle: RTCGeometry) {
+    let data = rtcGetGeometryUserData(handle) as *mut GeometryData;
+    if !data.is_null() {
+        rtcSetGeometryUserData(handle, ptr::null_mut());
+        drop(Box::from_raw(data));
+    }
+}
+
+/// Get primitive `i` from the index buffer of a mesh
+pub(crate) unsafe fn mesh_primitive<T: Copy>(geom: RTCGeometry, i: usize) -> T {
+    let indices = rtcGetGeometryBufferData(geom, BufferType::INDEX, 0) as *const T;
+    *indices.add(i)
+}
+
+/// Get vertex `i` from the vertex buffer of a mesh
+pub(crate) unsafe fn mesh_vertex(geom: RTCGeometry, i: u32) -> Vec3 {
+    let verts = rtcGetGeometryBufferData(geom, BufferType::VERTEX, 0) as *const [f32; 4];
+    let v = *verts.add(i as usize);
+    [v[0], v[1], v[2]]
+}
+
+#[test]
+fn test_backface_filter() {
+    use ray_packet::{Hit4, Ray4};
+
+    // All rays head along -z, hitting front faces with +z normals
+    let mut ray = Ray4::new([[0.0; 3]; 4], [[0.0, 0.0, -1.0]; 4]);
+    let mut hit = Hit4::new();
+    hit.Ng_z = [1.0, -1.0, 0.5, -0.5];
+    let mut valid = [-1, -1, -1, 0];
+    let args = RTCFilterFunctionNArguments {
+        valid: valid.as_mut_ptr(),
+        geometryUserPtr: ptr::null_mut(),
+        context: ptr::null_mut(),
+        ray: &mut ray as *mut Ray4 as *mut RTCRayN,
+        hit: &mut hit as *mut Hit4 as *mut RTCHitN,
+        N: 4,
+    };
+    unsafe {
+        backface_filter(&args);
+    }
+    assert_eq!(valid, [-1, 0, -1, 0]);
+}
@@ -1,8 +1,8 @@
-use cgmath::{Vector2, Vector3, Vector4};
+use mint::{Vector3, Vector4};
 
 use buffer::Buffer;
 use device::Device;
-use geometry::Geometry;
+use geometry::{self, EmbreeGeometry};
 use sys::*;
 use {BufferType, CurveType, Format, GeometryType};
 
@@ -163,4 +163,21 @@ impl<'a> HermiteCurve<'a> {
     }
 }
 
+unsafe impl<'a> EmbreeGeometry<'a> for HermiteCurve<'a> {
+    type Static = HermiteCurve<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for HermiteCurve<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
 unsafe impl<'a> Sync for HermiteCurve<'a> {}
@@ -1,10 +1,10 @@
 use std::os::raw;
 
-use cgmath::Matrix4;
+use mint::ColumnMatrix4;
 
 use device::Device;
-use geometry::Geometry;
-use scene::{CommittedScene, Scene};
+use geometry::{self, EmbreeGeometry};
+use scene::CommittedScene;
 use sys::*;
 use {BufferType, Format, GeometryType};
 
@@ -16,10 +16,14 @@ pub struct Instance<'a> {
 }
 
 impl<'a> Instance<'a> {
-    pub fn unanimated(device: &'a Device, scene: &'a CommittedScene) -> Instance<'a> {
+    /// Instance the committed `scene`, which is borrowed for as long as the
+    /// instance is alive, independent of how long the scene itself borrows
+    /// its device and buffers.
+    pub fn unanimated<'s>(device: &'a Device, scene: &'a CommittedScene<'s>) -> Instance<'a> {
         let h = unsafe { rtcNewGeometry(device.handle, GeometryType::INSTANCE) };
         unsafe {
-            rtcSetGeometryInstancedScene(h, scene.scene.handle);
+            rtcSetGeometryInstancedScene(h, scene.handle);
+            geometry::geometry_data_mut(h).instanced_scene = scene.handle;
         }
         Instance {
             device: device,
@@ -27,8 +31,13 @@ impl<'a> Instance<'a> {
             scene: scene,
         }
     }
-    pub fn set_transform(&mut self, transform: &Matrix4<f32>) {
-        let mat: &[f32; 16] = transform.as_ref();
+    /// The scene being instanced
+    pub(crate) fn instanced_scene(&self) -> &'a CommittedScene<'a> {
+        self.scene
+    }
+    /// Set the transform from the instance's local space to world space
+    pub fn set_transform<M: Into<ColumnMatrix4<f32>>>(&mut self, transform: M) {
+        let mat: [[f32; 4]; 4] = transform.into().into();
         // Will this be fine if we don't set the number of timesteps? Default should be 1?
         unsafe {
             rtcSetGeometryTransform(
@@ -41,4 +50,21 @@ impl<'a> Instance<'a> {
     }
 }
 
+unsafe impl<'a> EmbreeGeometry<'a> for Instance<'a> {
+    type Static = Instance<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for Instance<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
 unsafe impl<'a> Sync for Instance<'a> {}
@@ -0,0 +1,189 @@
+use std::{mem, u32};
+
+use ray::{Hit, IntersectContext, Ray};
+use sys::*;
+
+/// A context the `CommittedScene` queries can be made with, implemented by
+/// `IntersectContext` and `IntersectContextExt`.
+///
+/// # Safety
+/// `as_raw` must return a pointer to a valid context, covering any data
+/// after it which its callbacks read through the pointer, and stay valid
+/// while `self` is mutably borrowed.
+pub unsafe trait AsIntersectContext {
+    fn as_raw(&mut self) -> *mut RTCIntersectContext;
+}
+
+unsafe impl AsIntersectContext for IntersectContext {
+    fn as_raw(&mut self) -> *mut RTCIntersectContext {
+        self as *mut RTCIntersectContext
+    }
+}
+
+/// An intersection context extended with a user `payload` and an optional
+/// filter closure, for passing per-query state such as random seeds or path
+/// IDs to callbacks. It can be passed to any of the `CommittedScene` queries.
+///
+/// The filter is only called for scenes built with
+/// `SceneFlags::CONTEXT_FILTER_FUNCTION`.
+#[repr(C)]
+pub struct IntersectContextExt<'a, T> {
+    // Must be first, Embree passes a pointer to it to the callbacks
+    ctx: IntersectContext,
+    payload: T,
+    filter: Option<FilterFn<'a, T>>,
+}
+
+type FilterFn<'a, T> = Box<dyn FnMut(&mut T, &mut FilterArgs) + 'a>;
+
+impl<'a, T> IntersectContextExt<'a, T> {
+    pub fn coherent(payload: T) -> IntersectContextExt<'a, T> {
+        IntersectContextExt::new(IntersectContext::coherent(), payload)
+    }
+    pub fn incoherent(payload: T) -> IntersectContextExt<'a, T> {
+        IntersectContextExt::new(IntersectContext::incoherent(), payload)
+    }
+    fn new(ctx: IntersectContext, payload: T) -> IntersectContextExt<'a, T> {
+        IntersectContextExt {
+            ctx: ctx,
+            payload: payload,
+            filter: None,
+        }
+    }
+    /// Set a filter called with the payload for each hit found by queries
+    /// made with this context, after any geometry filter functions. Hits
+    /// can be ignored by rejecting them through the arguments.
+    pub fn filter<F>(mut self, filter: F) -> IntersectContextExt<'a, T>
+    where
+        F: FnMut(&mut T, &mut FilterArgs) + 'a,
+    {
+        self.ctx.filter = Some(filter_fn::<T>);
+        self.filter = Some(Box::new(filter));
+        self
+    }
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+    pub fn payload_mut(&mut self) -> &mut T {
+        &mut self.payload
+    }
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+    /// Get the extended context from the context passed to a callback,
+    /// e.g. a geometry filter or user geometry function.
+    ///
+    /// # Safety
+    /// `ctx` must point to the context of an `IntersectContextExt<T>`,
+    /// i.e. the query must have been made with one carrying a `T` payload.
+    pub unsafe fn from_raw<'b>(
+        ctx: *mut RTCIntersectContext,
+    ) -> &'b mut IntersectContextExt<'a, T> {
+        &mut *(ctx as *mut IntersectContextExt<'a, T>)
+    }
+}
+
+// The pointer is derived from the whole struct, as the filter reads the
+// payload and closure after the context through it
+unsafe impl<'a, T> AsIntersectContext for IntersectContextExt<'a, T> {
+    fn as_raw(&mut self) -> *mut RTCIntersectContext {
+        self as *mut IntersectContextExt<'a, T> as *mut RTCIntersectContext
+    }
+}
+
+/// The arguments passed to a filter for a packet of `len()` rays with
+/// potential hits, of which those that are `is_valid` should be filtered.
+pub struct FilterArgs<'a> {
+    args: &'a RTCFilterFunctionNArguments,
+}
+
+impl<'a> FilterArgs<'a> {
+    pub(crate) fn new(args: &'a RTCFilterFunctionNArguments) -> FilterArgs<'a> {
+        FilterArgs { args: args }
+    }
+    /// The number of rays in the packet
+    pub fn len(&self) -> usize {
+        self.args.N as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn is_valid(&self, i: usize) -> bool {
+        assert!(i < self.len());
+        unsafe { *self.args.valid.add(i) != 0 }
+    }
+    /// Reject the hit of ray `i`, so that traversal continues
+    /// as if the hit wasn't found.
+    pub fn reject(&mut self, i: usize) {
+        assert!(i < self.len());
+        unsafe {
+            *self.args.valid.add(i) = 0;
+        }
+    }
+    /// Get ray `i`, whose `tfar` is the distance to the hit
+    pub fn ray(&self, i: usize) -> Ray {
+        assert!(i < self.len());
+        unsafe { gather(self.args.ray as *const u32, self.len(), i) }
+    }
+    /// Get the hit being filtered for ray `i`
+    pub fn hit(&self, i: usize) -> Hit {
+        assert!(i < self.len());
+        unsafe { gather(self.args.hit as *const u32, self.len(), i) }
+    }
+}
+
+/// Read element `i` of the `RTCRayN`/`RTCHitN` packet of width `n` at
+/// `base`, which stores each 4 byte field of `T` in an array of `n` values.
+unsafe fn gather<T: Copy>(base: *const u32, n: usize, i: usize) -> T {
+    let mut v: T = mem::zeroed();
+    let dst = &mut v as *mut T as *mut u32;
+    for k in 0..mem::size_of::<T>() / 4 {
+        *dst.add(k) = *base.add(k * n + i);
+    }
+    v
+}
+
+unsafe extern "C" fn filter_fn<T>(args: *const RTCFilterFunctionNArguments) {
+    let args = &*args;
+    let ctx = IntersectContextExt::<T>::from_raw(args.context);
+    if let Some(ref mut filter) = ctx.filter {
+        let mut filter_args = FilterArgs { args: args };
+        filter(&mut ctx.payload, &mut filter_args);
+    }
+}
+
+#[test]
+fn test_context_filter() {
+    use ray_packet::{Hit4, Ray4, RayHit4};
+    use std::ptr;
+
+    // Reject the hits on odd primitives and count the accepted ones
+    let mut ctx = IntersectContextExt::coherent(0).filter(|accepted: &mut u32, args| {
+        for i in 0..args.len() {
+            if args.is_valid(i) && args.hit(i).prim_id() % 2 == 1 {
+                args.reject(i);
+            } else if args.is_valid(i) {
+                *accepted += 1;
+            }
+        }
+    });
+    let mut ray_hit = RayHit4::new(Ray4::empty());
+    ray_hit.ray.tfar = [1.0, 2.0, 3.0, 4.0];
+    ray_hit.hit = Hit4::new();
+    ray_hit.hit.primID = [0, 1, 2, 3];
+    let mut valid = [-1, -1, 0, -1];
+    let args = RTCFilterFunctionNArguments {
+        valid: valid.as_mut_ptr(),
+        geometryUserPtr: ptr::null_mut(),
+        context: ctx.as_raw(),
+        ray: &mut ray_hit.ray as *mut Ray4 as *mut RTCRayN,
+        hit: &mut ray_hit.hit as *mut Hit4 as *mut RTCHitN,
+        N: 4,
+    };
+    unsafe {
+        (ctx.ctx.filter.unwrap())(&args);
+    }
+    assert_eq!(valid, [-1, 0, 0, 0]);
+    assert_eq!(*ctx.payload(), 1);
+    assert_eq!(FilterArgs { args: &args }.ray(3).tfar, 4.0);
+}
@@ -10,21 +10,49 @@
 //! Embree documentation can be found [here](https://embree.github.io/api.html).
 //! See the [examples/](https://github.com/Twinklebear/embree-rs/tree/master/examples)
 //! for some example applications using the bindings.
+//!
+//! # Math Types
+//!
+//! Vectors and matrices are passed to and returned from the API as
+//! [mint](https://docs.rs/mint/) types, so any math library with mint
+//! conversions can be used, e.g. `cgmath` (with its `mint` feature),
+//! `glam` or `nalgebra`. Functions taking a vector accept anything that's
+//! `Into<mint::Vector3<f32>>`, including plain `[f32; 3]` arrays, and the
+//! returned mint types convert back with `into()`.
+//!
+//! The `cgmath`, `glam` and `nalgebra` features re-export a version of each
+//! crate with its mint conversions enabled, so they're guaranteed to work
+//! with the types used by the crate. No math library is required otherwise.
 
-use std::{alloc, mem};
-
-extern crate cgmath;
+#[cfg(feature = "cgmath")]
+pub extern crate cgmath;
+#[cfg(feature = "glam")]
+pub extern crate glam;
+pub extern crate mint;
+#[cfg(feature = "nalgebra")]
+pub extern crate nalgebra;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
+pub mod aabb;
+pub mod aligned_vec;
 pub mod bezier_curve;
 pub mod bspline_curve;
 pub mod buffer;
+pub mod bvh;
 pub mod catmull_rom_curve;
+pub mod collision;
 pub mod curve;
 pub mod device;
 pub mod geometry;
 pub mod hermite_curve;
 pub mod instance;
+pub mod intersect_context;
 pub mod linear_curve;
+mod math;
+#[cfg(feature = "rayon")]
+mod parallel;
+pub mod point_query;
 pub mod quad_mesh;
 pub mod ray;
 pub mod ray_packet;
@@ -37,21 +65,33 @@ pub mod soa_ray;
 pub mod sys;
 pub mod triangle_mesh;
 
+pub use aabb::Aabb;
+pub use aligned_vec::AlignedVec;
 pub use bezier_curve::BezierCurve;
 pub use bspline_curve::BsplineCurve;
 pub use buffer::{Buffer, MappedBuffer};
+pub use bvh::{BuildPrimitive, BvhBuilder, BvhNode};
 pub use catmull_rom_curve::CatmullRomCurve;
+pub use collision::{Collision, CollisionMesh};
 pub use curve::CurveType;
 pub use device::Device;
-pub use geometry::Geometry;
+pub use geometry::EmbreeGeometry;
 pub use hermite_curve::HermiteCurve;
 pub use instance::Instance;
+pub use intersect_context::{AsIntersectContext, FilterArgs, IntersectContextExt};
 pub use linear_curve::LinearCurve;
+pub use point_query::{
+    ClosestPoint, PointQuery, PointQuery16, PointQuery4, PointQuery8, PointQueryArgs,
+    PointQueryContext, SoAPointQuery,
+};
 pub use quad_mesh::QuadMesh;
-pub use ray::{Hit, IntersectContext, Ray, RayHit};
-pub use ray_packet::{Hit4, Ray4, RayHit4};
-pub use ray_stream::{HitN, RayHitN, RayN};
-pub use scene::{CommittedScene, Scene};
+pub use ray::{Hit, IntersectContext, Intersection, Ray, RayHit};
+pub use ray_packet::{
+    Hit16, Hit4, Hit8, HitPacket, LaneCount, Ray16, Ray4, Ray8, RayHit16, RayHit4, RayHit8,
+    RayHitPacket, RayPacket, SoARayHit, SupportedLaneCount, ValidMask,
+};
+pub use ray_stream::{HitN, HitNpRef, RayHitN, RayHitNpRef, RayN, RayNpRef};
+pub use scene::{CommittedScene, Scene, SceneBuilder};
 pub use soa_ray::{
     SoAHit, SoAHitIter, SoAHitIterMut, SoAHitRef, SoARay, SoARayIter, SoARayIterMut, SoARayRef,
     SoARayRefMut,
@@ -72,34 +112,3 @@ pub use sys::RTCBuildFlags as BuildFlags;
 pub use sys::RTCCurveFlags as CurveFlags;
 pub use sys::RTCIntersectContextFlags as IntersectContextFlags;
 pub use sys::RTCSceneFlags as SceneFlags;
-
-/// Utility for making specifically aligned vectors
-pub fn aligned_vector<T>(len: usize, align: usize) -> Vec<T> {
-    let t_size = mem::size_of::<T>();
-    let t_align = mem::align_of::<T>();
-    let layout = if t_align >= align {
-        alloc::Layout::from_size_align(t_size * len, t_align).unwrap()
-    } else {
-        alloc::Layout::from_size_align(t_size * len, align).unwrap()
-    };
-    unsafe {
-        let mem = alloc::alloc(layout);
-        assert_eq!((mem as usize) % 16, 0);
-        Vec::<T>::from_raw_parts(mem as *mut T, len, len)
-    }
-}
-pub fn aligned_vector_init<T: Copy>(len: usize, align: usize, init: T) -> Vec<T> {
-    let mut v = aligned_vector::<T>(len, align);
-    for x in v.iter_mut() {
-        *x = init;
-    }
-    v
-}
-
-#[test]
-fn test_aligned_vector_alloc() {
-    let v = aligned_vector_init::<f32>(24, 16, 1.0);
-    for x in v.iter() {
-        assert_eq!(*x, 1.0);
-    }
-}
@@ -1,8 +1,8 @@
-use cgmath::{Vector2, Vector3, Vector4};
+use mint::{Vector3, Vector4};
 
 use buffer::Buffer;
 use device::Device;
-use geometry::Geometry;
+use geometry::{self, EmbreeGeometry};
 use sys::*;
 use {BufferType, CurveType, Format, GeometryType};
 
@@ -145,4 +145,21 @@ impl<'a> LinearCurve<'a> {
     }
 }
 
+unsafe impl<'a> EmbreeGeometry<'a> for LinearCurve<'a> {
+    type Static = LinearCurve<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for LinearCurve<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
 unsafe impl<'a> Sync for LinearCurve<'a> {}
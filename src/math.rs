@@ -0,0 +1,52 @@
+// The little vector math the crate needs internally, on plain arrays so it
+// doesn't depend on any particular math library.
+
+pub(crate) type Vec3 = [f32; 3];
+
+pub(crate) fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(a: Vec3, s: f32) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+pub(crate) fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn distance2(a: Vec3, b: Vec3) -> f32 {
+    let d = sub(a, b);
+    dot(d, d)
+}
+
+/// Transform the point `p` by the column major 4x4 matrix `m`
+pub(crate) fn transform_point(m: &[f32; 16], p: Vec3) -> Vec3 {
+    let mut out = [m[12], m[13], m[14]];
+    for (r, o) in out.iter_mut().enumerate() {
+        *o += m[r] * p[0] + m[4 + r] * p[1] + m[8 + r] * p[2];
+    }
+    out
+}
+
+#[test]
+fn test_transform_point() {
+    // Scale by 2 along x then translate by (1, 2, 3)
+    let m = [
+        2.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 2.0, 3.0, 1.0,
+    ];
+    assert_eq!(transform_point(&m, [1.0, 1.0, 1.0]), [3.0, 3.0, 4.0]);
+    assert_eq!(cross([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]), [0.0, 0.0, 1.0]);
+}
@@ -0,0 +1,42 @@
+use rayon;
+use rayon::prelude::*;
+
+use ray::{IntersectContext, RayHit};
+use ray_stream::{RayHitN, RayHitNpRef};
+use scene::CommittedScene;
+
+/// The number of rays traced together by each task of `par_intersect_soa`
+const SOA_CHUNK_SIZE: usize = 256;
+
+impl<'a> CommittedScene<'a> {
+    /// Intersect the rays with the scene in parallel on the rayon thread
+    /// pool. The rays are split into contiguous chunks of `chunk` rays, each
+    /// traced as a stream with its own `IntersectContext`, so rays which are
+    /// close in the slice should be coherent.
+    pub fn par_intersect(&self, rays: &mut [RayHit], chunk: usize) {
+        rays.par_chunks_mut(chunk.max(1)).for_each(|rays| {
+            let mut ctx = IntersectContext::coherent();
+            self.intersect_stream_aos(&mut ctx, rays);
+        });
+    }
+    /// Intersect a ray stream in SoA format with the scene in parallel
+    /// on the rayon thread pool, see `par_intersect`.
+    pub fn par_intersect_soa(&self, rays: &mut RayHitN) {
+        self.par_intersect_soa_ref(rays.np_ref());
+    }
+    fn par_intersect_soa_ref(&self, mut rays: RayHitNpRef) {
+        let n = rays.len();
+        if n <= SOA_CHUNK_SIZE {
+            let mut ctx = IntersectContext::coherent();
+            self.intersect_stream_soa_ref(&mut ctx, &mut rays);
+        } else {
+            // Split on a multiple of the chunk size to keep both halves aligned
+            let mid = (n / 2).div_ceil(SOA_CHUNK_SIZE) * SOA_CHUNK_SIZE;
+            let (left, right) = rays.split_at_mut(mid);
+            rayon::join(
+                || self.par_intersect_soa_ref(left),
+                || self.par_intersect_soa_ref(right),
+            );
+        }
+    }
+}
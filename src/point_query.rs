@@ -0,0 +1,397 @@
+use mint;
+use std::{f32, u32};
+
+use geometry;
+use math::{self, Vec3};
+use sys::*;
+use GeometryType;
+
+pub type PointQuery = RTCPointQuery;
+pub type PointQueryContext = RTCPointQueryContext;
+
+impl PointQuery {
+    /// Create a new query for the geometry within `radius` of `point`
+    pub fn new<V: Into<mint::Vector3<f32>>>(point: V, radius: f32) -> PointQuery {
+        let point = point.into();
+        RTCPointQuery {
+            x: point.x,
+            y: point.y,
+            z: point.z,
+            time: 0.0,
+            radius: radius,
+        }
+    }
+    pub fn point(&self) -> mint::Vector3<f32> {
+        mint::Vector3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
+    }
+}
+
+impl PointQueryContext {
+    /// Create a new context with an empty instance stack, the
+    /// equivalent of `rtcInitPointQueryContext`
+    pub fn new() -> PointQueryContext {
+        RTCPointQueryContext {
+            world2inst: [[0.0; 16]; 1],
+            inst2world: [[0.0; 16]; 1],
+            instID: [u32::MAX; 1],
+            instStackSize: 0,
+        }
+    }
+}
+
+impl Default for PointQueryContext {
+    fn default() -> PointQueryContext {
+        PointQueryContext::new()
+    }
+}
+
+pub type PointQuery4 = RTCPointQuery4;
+pub type PointQuery8 = RTCPointQuery8;
+pub type PointQuery16 = RTCPointQuery16;
+
+/// Accessors for the queries in a packet of point queries
+pub trait SoAPointQuery {
+    fn point(&self, i: usize) -> mint::Vector3<f32>;
+    fn set_point(&mut self, i: usize, p: mint::Vector3<f32>);
+
+    fn time(&self, i: usize) -> f32;
+    fn set_time(&mut self, i: usize, time: f32);
+
+    fn radius(&self, i: usize) -> f32;
+    fn set_radius(&mut self, i: usize, radius: f32);
+}
+
+macro_rules! point_query_packet {
+    ($packet:ident, $n:expr) => {
+        impl $packet {
+            /// Create a new packet of queries for the geometry within
+            /// `radius[i]` of `points[i]`
+            pub fn new<V>(points: [V; $n], radius: [f32; $n]) -> $packet
+            where
+                V: Into<mint::Vector3<f32>> + Copy,
+            {
+                let mut query = $packet {
+                    x: [0.0; $n],
+                    y: [0.0; $n],
+                    z: [0.0; $n],
+                    time: [0.0; $n],
+                    radius: radius,
+                };
+                for (i, p) in points.iter().enumerate() {
+                    query.set_point(i, (*p).into());
+                }
+                query
+            }
+        }
+
+        impl SoAPointQuery for $packet {
+            fn point(&self, i: usize) -> mint::Vector3<f32> {
+                mint::Vector3 {
+                    x: self.x[i],
+                    y: self.y[i],
+                    z: self.z[i],
+                }
+            }
+            fn set_point(&mut self, i: usize, p: mint::Vector3<f32>) {
+                self.x[i] = p.x;
+                self.y[i] = p.y;
+                self.z[i] = p.z;
+            }
+
+            fn time(&self, i: usize) -> f32 {
+                self.time[i]
+            }
+            fn set_time(&mut self, i: usize, time: f32) {
+                self.time[i] = time;
+            }
+
+            fn radius(&self, i: usize) -> f32 {
+                self.radius[i]
+            }
+            fn set_radius(&mut self, i: usize, radius: f32) {
+                self.radius[i] = radius;
+            }
+        }
+    };
+}
+
+point_query_packet!(PointQuery4, 4);
+point_query_packet!(PointQuery8, 8);
+point_query_packet!(PointQuery16, 16);
+
+/// The closest point on the scene geometry to a query point,
+/// found by `CommittedScene::closest_point`.
+#[derive(Debug, Copy, Clone)]
+pub struct ClosestPoint {
+    pub point: mint::Vector3<f32>,
+    pub distance: f32,
+    pub prim_id: u32,
+    pub geom_id: u32,
+    pub inst_id: u32,
+}
+
+/// The arguments passed to the point query callback for each
+/// primitive whose bounds overlap the query.
+pub struct PointQueryArgs<'a> {
+    args: &'a mut RTCPointQueryFunctionArguments,
+    scene: RTCScene,
+}
+
+impl<'a> PointQueryArgs<'a> {
+    pub(crate) fn new(
+        args: &'a mut RTCPointQueryFunctionArguments,
+        scene: RTCScene,
+    ) -> PointQueryArgs<'a> {
+        PointQueryArgs {
+            args: args,
+            scene: scene,
+        }
+    }
+    /// The query being made. If the primitive belongs to an instance with a
+    /// similarity transform, the query is in the instance's local space,
+    /// otherwise it's in world space. See `similarity_scale`.
+    pub fn query(&self) -> &PointQuery {
+        unsafe { &*self.args.query }
+    }
+    /// The query being made, whose radius can be reduced to cull primitives
+    /// further than the closest found so far. The callback should return
+    /// `true` when it changes the query.
+    pub fn query_mut(&mut self) -> &mut PointQuery {
+        unsafe { &mut *self.args.query }
+    }
+    pub fn prim_id(&self) -> u32 {
+        self.args.primID
+    }
+    pub fn geom_id(&self) -> u32 {
+        self.args.geomID
+    }
+    /// The ID of the instance the primitive belongs to, or `u32::MAX`
+    /// if it's not instanced.
+    pub fn inst_id(&self) -> u32 {
+        let ctx = unsafe { &*self.args.context };
+        if ctx.instStackSize > 0 {
+            ctx.instID[0]
+        } else {
+            u32::MAX
+        }
+    }
+    /// The scale from world to instance space when the primitive belongs to
+    /// an instance with a similarity transform, in which case the query has been
+    /// transformed into the instance's local space. Otherwise this is 0 and the
+    /// query is in world space.
+    pub fn similarity_scale(&self) -> f32 {
+        self.args.similarityScale
+    }
+    /// The transform from the local space of the instance the primitive
+    /// belongs to into world space, if it's instanced
+    pub fn instance_to_world(&self) -> Option<mint::ColumnMatrix4<f32>> {
+        let ctx = unsafe { &*self.args.context };
+        if ctx.instStackSize > 0 {
+            Some(ctx.inst2world[0].into())
+        } else {
+            None
+        }
+    }
+    /// The transform from world space into the local space of the instance
+    /// the primitive belongs to, if it's instanced
+    pub fn world_to_instance(&self) -> Option<mint::ColumnMatrix4<f32>> {
+        let ctx = unsafe { &*self.args.context };
+        if ctx.instStackSize > 0 {
+            Some(ctx.world2inst[0].into())
+        } else {
+            None
+        }
+    }
+    /// The query point in world space
+    pub fn world_point(&self) -> mint::Vector3<f32> {
+        let p = self.query().point();
+        match self.instance_to_world() {
+            Some(m) if self.similarity_scale() > 0.0 => {
+                math::transform_point(&m.into(), p.into()).into()
+            }
+            _ => p,
+        }
+    }
+    /// Set the radius of the query to `radius` in world space, scaling it
+    /// to the instance's local space if the query has been transformed.
+    pub fn set_world_radius(&mut self, radius: f32) {
+        let scale = if self.inst_id() != u32::MAX && self.similarity_scale() > 0.0 {
+            self.similarity_scale()
+        } else {
+            1.0
+        };
+        self.query_mut().radius = radius * scale;
+    }
+    /// Compute the closest point in world space on the primitive, if it
+    /// belongs to a `TriangleMesh` or `QuadMesh`. For instances without a
+    /// similarity transform the closest point is found in the instance's local
+    /// space, which is not exact when the instance is non-uniformly scaled.
+    pub fn closest_point(&self) -> Option<mint::Vector3<f32>> {
+        let inst_to_world: Option<[f32; 16]> = self.instance_to_world().map(|m| m.into());
+        let scene = match inst_to_world {
+            Some(_) => unsafe {
+                let inst = rtcGetGeometry(self.scene, self.inst_id());
+                geometry::geometry_data(inst)?.instanced_scene
+            },
+            None => self.scene,
+        };
+        let query_point: Vec3 = self.query().point().into();
+        let local_point = match self.world_to_instance() {
+            Some(m) if self.similarity_scale() == 0.0 => {
+                math::transform_point(&m.into(), query_point)
+            }
+            _ => query_point,
+        };
+        let p = unsafe {
+            let geom = rtcGetGeometry(scene, self.geom_id());
+            let prim = self.prim_id() as usize;
+            match geometry::geometry_data(geom)?.geometry_type? {
+                GeometryType::TRIANGLE => {
+                    let tri = geometry::mesh_primitive::<[u32; 3]>(geom, prim);
+                    closest_point_on_triangle(
+                        local_point,
+                        geometry::mesh_vertex(geom, tri[0]),
+                        geometry::mesh_vertex(geom, tri[1]),
+                        geometry::mesh_vertex(geom, tri[2]),
+                    )
+                }
+                GeometryType::QUAD => {
+                    let quad = geometry::mesh_primitive::<[u32; 4]>(geom, prim);
+                    let (v0, v1, v2, v3) = (
+                        geometry::mesh_vertex(geom, quad[0]),
+                        geometry::mesh_vertex(geom, quad[1]),
+                        geometry::mesh_vertex(geom, quad[2]),
+                        geometry::mesh_vertex(geom, quad[3]),
+                    );
+                    // Embree splits quads into the triangles (v0, v1, v3) and (v2, v3, v1)
+                    let a = closest_point_on_triangle(local_point, v0, v1, v3);
+                    let b = closest_point_on_triangle(local_point, v2, v3, v1);
+                    if math::distance2(a, local_point) <= math::distance2(b, local_point) {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                _ => return None,
+            }
+        };
+        match inst_to_world {
+            Some(m) => Some(math::transform_point(&m, p).into()),
+            None => Some(p.into()),
+        }
+    }
+}
+
+/// Update `closest` with the closest point on the primitive visited by the
+/// query if it's closer than the closest found so far, or `radius` if none has
+/// been found yet, shrinking the query radius to match. Returns `true` if
+/// the query radius was changed.
+pub(crate) fn update_closest_point(
+    args: &mut PointQueryArgs,
+    closest: &mut Option<ClosestPoint>,
+    radius: f32,
+) -> bool {
+    let p = match args.closest_point() {
+        Some(p) => p,
+        None => return false,
+    };
+    let d = math::distance2(p.into(), args.world_point().into()).sqrt();
+    if d < closest.map_or(radius, |c| c.distance) {
+        *closest = Some(ClosestPoint {
+            point: p,
+            distance: d,
+            prim_id: args.prim_id(),
+            geom_id: args.geom_id(),
+            inst_id: args.inst_id(),
+        });
+        args.set_world_radius(d);
+        true
+    } else {
+        false
+    }
+}
+
+/// Find the closest point to `p` on the triangle `(a, b, c)`, from
+/// Ericson's Real-Time Collision Detection, Section 5.1.5
+pub fn closest_point_triangle<V: Into<mint::Vector3<f32>>>(
+    p: V,
+    a: V,
+    b: V,
+    c: V,
+) -> mint::Vector3<f32> {
+    closest_point_on_triangle(
+        p.into().into(),
+        a.into().into(),
+        b.into().into(),
+        c.into().into(),
+    )
+    .into()
+}
+
+fn closest_point_on_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let ab = math::sub(b, a);
+    let ac = math::sub(c, a);
+    let ap = math::sub(p, a);
+    let d1 = math::dot(ab, ap);
+    let d2 = math::dot(ac, ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = math::sub(p, b);
+    let d3 = math::dot(ab, bp);
+    let d4 = math::dot(ac, bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let cp = math::sub(p, c);
+    let d5 = math::dot(ab, cp);
+    let d6 = math::dot(ac, cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return math::add(a, math::scale(ab, v));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let v = d2 / (d2 - d6);
+        return math::add(a, math::scale(ac, v));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let v = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return math::add(b, math::scale(math::sub(c, b), v));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    let v = vb * denom;
+    let w = vc * denom;
+    math::add(a, math::add(math::scale(ab, v), math::scale(ac, w)))
+}
+
+#[test]
+fn test_closest_point_triangle() {
+    let a = [0.0, 0.0, 0.0];
+    let b = [1.0, 0.0, 0.0];
+    let c = [0.0, 1.0, 0.0];
+    // Above the interior, in the vertex a, edge bc and vertex c regions
+    let p = closest_point_triangle([0.25, 0.25, 1.0], a, b, c);
+    assert_eq!(p, [0.25, 0.25, 0.0].into());
+    let p = closest_point_triangle([-1.0, -1.0, 0.0], a, b, c);
+    assert_eq!(p, a.into());
+    let p = closest_point_triangle([1.0, 1.0, 0.0], a, b, c);
+    assert_eq!(p, [0.5, 0.5, 0.0].into());
+    let p = closest_point_triangle([-0.5, 2.0, 0.0], a, b, c);
+    assert_eq!(p, c.into());
+}
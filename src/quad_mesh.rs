@@ -1,8 +1,8 @@
-use cgmath::Vector4;
+use mint::Vector4;
 
 use buffer::Buffer;
 use device::Device;
-use geometry::Geometry;
+use geometry::{self, EmbreeGeometry};
 use sys::*;
 use {BufferType, Format, GeometryType};
 
@@ -42,6 +42,9 @@ impl<'a> QuadMesh<'a> {
                 num_quads,
             );
             index_buffer.set_attachment(h, BufferType::INDEX, 0);
+
+            // Tag the mesh so closest point queries can find its buffers
+            geometry::geometry_data_mut(h).geometry_type = Some(GeometryType::QUAD);
         }
         QuadMesh {
             device: device,
@@ -52,4 +55,21 @@ impl<'a> QuadMesh<'a> {
     }
 }
 
+unsafe impl<'a> EmbreeGeometry<'a> for QuadMesh<'a> {
+    type Static = QuadMesh<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for QuadMesh<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
 unsafe impl<'a> Sync for QuadMesh<'a> {}
@@ -1,4 +1,4 @@
-use cgmath::Vector3;
+use mint::Vector3;
 use std::{f32, u32};
 
 use sys;
@@ -10,10 +10,20 @@ pub type IntersectContext = sys::RTCIntersectContext;
 
 impl Ray {
     /// Create a new ray starting at `origin` and heading in direction `dir`
-    pub fn new(origin: Vector3<f32>, dir: Vector3<f32>) -> Ray {
+    pub fn new<O, D>(origin: O, dir: D) -> Ray
+    where
+        O: Into<Vector3<f32>>,
+        D: Into<Vector3<f32>>,
+    {
         Ray::segment(origin, dir, 0.0, f32::INFINITY)
     }
-    pub fn segment(origin: Vector3<f32>, dir: Vector3<f32>, tnear: f32, tfar: f32) -> Ray {
+    pub fn segment<O, D>(origin: O, dir: D, tnear: f32, tfar: f32) -> Ray
+    where
+        O: Into<Vector3<f32>>,
+        D: Into<Vector3<f32>>,
+    {
+        let origin = origin.into();
+        let dir = dir.into();
         sys::RTCRay {
             org_x: origin.x,
             org_y: origin.y,
@@ -29,6 +39,51 @@ impl Ray {
             flags: 0,
         }
     }
+    pub fn origin(&self) -> Vector3<f32> {
+        Vector3 {
+            x: self.org_x,
+            y: self.org_y,
+            z: self.org_z,
+        }
+    }
+    pub fn dir(&self) -> Vector3<f32> {
+        Vector3 {
+            x: self.dir_x,
+            y: self.dir_y,
+            z: self.dir_z,
+        }
+    }
+    /// Get the point at distance `t` along the ray, in units of the
+    /// length of the ray direction
+    pub fn point_at(&self, t: f32) -> Vector3<f32> {
+        Vector3 {
+            x: self.org_x + t * self.dir_x,
+            y: self.org_y + t * self.dir_y,
+            z: self.org_z + t * self.dir_z,
+        }
+    }
+    pub fn with_tnear(mut self, tnear: f32) -> Ray {
+        self.tnear = tnear;
+        self
+    }
+    pub fn with_tfar(mut self, tfar: f32) -> Ray {
+        self.tfar = tfar;
+        self
+    }
+    /// Set the time of the ray for motion blur, in [0, 1]
+    pub fn with_time(mut self, time: f32) -> Ray {
+        self.time = time;
+        self
+    }
+    /// Set the ray mask, the ray only hits geometry whose mask shares a set bit
+    pub fn with_mask(mut self, mask: u32) -> Ray {
+        self.mask = mask;
+        self
+    }
+    pub fn with_id(mut self, id: u32) -> Ray {
+        self.id = id;
+        self
+    }
 }
 
 impl Hit {
@@ -47,6 +102,54 @@ impl Hit {
     pub fn hit(&self) -> bool {
         self.geomID != u32::MAX
     }
+    /// The unnormalized geometric normal of the hit, in the object space of
+    /// the geometry hit. For hits on instanced geometry it must be transformed
+    /// by the inverse transpose of the instance's transform to get the normal
+    /// in world space.
+    pub fn normal(&self) -> Vector3<f32> {
+        Vector3 {
+            x: self.Ng_x,
+            y: self.Ng_y,
+            z: self.Ng_z,
+        }
+    }
+    /// The barycentric coordinates of the hit on the primitive
+    pub fn uv(&self) -> (f32, f32) {
+        (self.u, self.v)
+    }
+    pub fn prim_id(&self) -> u32 {
+        self.primID
+    }
+    pub fn geom_id(&self) -> u32 {
+        self.geomID
+    }
+    /// The IDs of the instances the hit geometry is in, from the top level
+    /// instance down. Empty if the geometry isn't instanced.
+    pub fn instance_ids(&self) -> &[u32] {
+        let levels = self
+            .instID
+            .iter()
+            .position(|id| *id == u32::MAX)
+            .unwrap_or(self.instID.len());
+        &self.instID[..levels]
+    }
+}
+
+/// A hit found along a ray, e.g. by `CommittedScene::intersect_ray`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Intersection {
+    /// The distance to the hit, in units of the length of the ray direction
+    pub distance: f32,
+    /// The point hit
+    pub point: Vector3<f32>,
+    /// The unnormalized geometric normal, see `Hit::normal`
+    pub normal: Vector3<f32>,
+    /// The barycentric coordinates of the hit on the primitive
+    pub uv: (f32, f32),
+    pub prim_id: u32,
+    pub geom_id: u32,
+    /// The ID of the instance the hit geometry is in, if it's instanced
+    pub inst_id: Option<u32>,
 }
 
 impl RayHit {
@@ -56,6 +159,26 @@ impl RayHit {
             hit: Hit::new(),
         }
     }
+    /// The point where the ray hit the scene, if it hit anything
+    pub fn hit_point(&self) -> Option<Vector3<f32>> {
+        if self.hit.hit() {
+            Some(self.ray.point_at(self.ray.tfar))
+        } else {
+            None
+        }
+    }
+    /// Get the hit found for the ray, if it hit anything
+    pub fn intersection(&self) -> Option<Intersection> {
+        self.hit_point().map(|point| Intersection {
+            distance: self.ray.tfar,
+            point: point,
+            normal: self.hit.normal(),
+            uv: self.hit.uv(),
+            prim_id: self.hit.prim_id(),
+            geom_id: self.hit.geom_id(),
+            inst_id: self.hit.instance_ids().first().copied(),
+        })
+    }
 }
 
 impl IntersectContext {
@@ -65,6 +188,16 @@ impl IntersectContext {
     pub fn incoherent() -> IntersectContext {
         IntersectContext::new(sys::RTCIntersectContextFlags::INCOHERENT)
     }
+    /// The IDs of the instances entered so far during traversal, from the
+    /// top level instance down, e.g. when called back from user geometry
+    pub fn instance_ids(&self) -> &[u32] {
+        let levels = self
+            .instID
+            .iter()
+            .position(|id| *id == u32::MAX)
+            .unwrap_or(self.instID.len());
+        &self.instID[..levels]
+    }
     fn new(flags: sys::RTCIntersectContextFlags) -> IntersectContext {
         sys::RTCIntersectContext {
             flags: flags,
@@ -73,3 +206,52 @@ impl IntersectContext {
         }
     }
 }
+
+#[test]
+fn test_ray_hit_accessors() {
+    let ray = Ray::new([1.0, 0.0, 0.0], [0.0, 2.0, 0.0])
+        .with_tnear(0.5)
+        .with_mask(0x1);
+    assert_eq!(ray.origin(), Vector3::from([1.0, 0.0, 0.0]));
+    assert_eq!(ray.dir(), Vector3::from([0.0, 2.0, 0.0]));
+    assert_eq!((ray.tnear, ray.tfar, ray.mask), (0.5, f32::INFINITY, 0x1));
+
+    let mut ray_hit = RayHit::new(ray);
+    assert_eq!(ray_hit.hit_point(), None);
+    assert_eq!(ray_hit.intersection(), None);
+    assert!(ray_hit.hit.instance_ids().is_empty());
+    ray_hit.ray.tfar = 1.5;
+    ray_hit.hit.geomID = 0;
+    ray_hit.hit.instID[0] = 3;
+    assert_eq!(ray_hit.hit_point(), Some(Vector3::from([1.0, 3.0, 0.0])));
+    assert_eq!(ray_hit.hit.instance_ids(), &[3]);
+    let hit = ray_hit.intersection().unwrap();
+    assert_eq!((hit.distance, hit.geom_id, hit.inst_id), (1.5, 0, Some(3)));
+}
+
+#[test]
+#[cfg(feature = "glam")]
+fn test_glam_interop() {
+    use glam::Vec3;
+    let ray = Ray::new(Vec3::new(1.0, 0.0, 0.0), Vec3::Y);
+    let p: Vec3 = ray.point_at(2.0).into();
+    assert_eq!(p, Vec3::new(1.0, 2.0, 0.0));
+}
+
+#[test]
+#[cfg(feature = "nalgebra")]
+fn test_nalgebra_interop() {
+    use nalgebra::Vector3;
+    let ray = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::y());
+    let p: Vector3<f32> = ray.point_at(2.0).into();
+    assert_eq!(p, Vector3::new(1.0, 2.0, 0.0));
+}
+
+#[test]
+#[cfg(feature = "cgmath")]
+fn test_cgmath_interop() {
+    use cgmath::Vector3;
+    let ray = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::unit_y());
+    let p: Vector3<f32> = ray.point_at(2.0).into();
+    assert_eq!(p, Vector3::new(1.0, 2.0, 0.0));
+}
@@ -1,4 +1,4 @@
-use cgmath::Vector3;
+use mint::Vector3;
 use std::marker::PhantomData;
 use std::{f32, u32};
 
@@ -6,192 +6,410 @@ use soa_ray::{
     SoAHit, SoAHitIter, SoAHitIterMut, SoAHitRef, SoARay, SoARayIter, SoARayIterMut, SoARayRef,
     SoARayRefMut,
 };
-use sys;
+use sys::{self, RTCIntersectContext, RTCScene};
 
 pub type Ray4 = sys::RTCRay4;
 pub type Hit4 = sys::RTCHit4;
 pub type RayHit4 = sys::RTCRayHit4;
 
-impl Ray4 {
-    pub fn empty() -> Ray4 {
-        Ray4::segment(
-            [Vector3::new(0.0, 0.0, 0.0); 4],
-            [Vector3::new(0.0, 0.0, 0.0); 4],
-            [0.0; 4],
-            [f32::INFINITY; 4],
-        )
-    }
-    pub fn new(origin: [Vector3<f32>; 4], dir: [Vector3<f32>; 4]) -> Ray4 {
-        Ray4::segment(origin, dir, [0.0; 4], [f32::INFINITY; 4])
-    }
-    pub fn segment(
-        origin: [Vector3<f32>; 4],
-        dir: [Vector3<f32>; 4],
-        tnear: [f32; 4],
-        tfar: [f32; 4],
-    ) -> Ray4 {
-        sys::RTCRay4 {
-            org_x: [origin[0].x, origin[1].x, origin[2].x, origin[3].x],
-            org_y: [origin[0].y, origin[1].y, origin[2].y, origin[3].y],
-            org_z: [origin[0].z, origin[1].z, origin[2].z, origin[3].z],
-            dir_x: [dir[0].x, dir[1].x, dir[2].x, dir[3].x],
-            dir_y: [dir[0].y, dir[1].y, dir[2].y, dir[3].y],
-            dir_z: [dir[0].z, dir[1].z, dir[2].z, dir[3].z],
-            tnear: tnear,
-            tfar: tfar,
-            time: [0.0; 4],
-            mask: [u32::MAX; 4],
-            id: [0; 4],
-            flags: [0; 4],
-        }
-    }
-    pub fn iter(&self) -> SoARayIter<Ray4> {
-        SoARayIter::new(self, 4)
-    }
-    pub fn iter_mut(&mut self) -> SoARayIterMut<Ray4> {
-        SoARayIterMut::new(self, 4)
-    }
-}
+pub type Ray8 = sys::RTCRay8;
+pub type Hit8 = sys::RTCHit8;
+pub type RayHit8 = sys::RTCRayHit8;
 
-impl SoARay for Ray4 {
-    fn org(&self, i: usize) -> Vector3<f32> {
-        Vector3::new(self.org_x[i], self.org_y[i], self.org_z[i])
-    }
-    fn set_org(&mut self, i: usize, o: Vector3<f32>) {
-        self.org_x[i] = o.x;
-        self.org_y[i] = o.y;
-        self.org_z[i] = o.z;
-    }
+pub type Ray16 = sys::RTCRay16;
+pub type Hit16 = sys::RTCHit16;
+pub type RayHit16 = sys::RTCRayHit16;
 
-    fn dir(&self, i: usize) -> Vector3<f32> {
-        Vector3::new(self.dir_x[i], self.dir_y[i], self.dir_z[i])
-    }
-    fn set_dir(&mut self, i: usize, d: Vector3<f32>) {
-        self.dir_x[i] = d.x;
-        self.dir_y[i] = d.y;
-        self.dir_z[i] = d.z;
-    }
+/// The ray packet with `N` rays, one of `Ray4`, `Ray8` or `Ray16`
+pub type RayPacket<const N: usize> = <LaneCount<N> as SupportedLaneCount>::Ray;
+/// The hit packet with `N` hits, one of `Hit4`, `Hit8` or `Hit16`
+pub type HitPacket<const N: usize> = <LaneCount<N> as SupportedLaneCount>::Hit;
+/// The combined ray and hit packet with `N` rays, one of `RayHit4`,
+/// `RayHit8` or `RayHit16`
+pub type RayHitPacket<const N: usize> = <LaneCount<N> as SupportedLaneCount>::RayHit;
 
-    fn tnear(&self, i: usize) -> f32 {
-        self.tnear[i]
-    }
-    fn set_tnear(&mut self, i: usize, near: f32) {
-        self.tnear[i] = near;
-    }
+/// Marker type for the packet width `N`, which is only a supported width
+/// if it implements `SupportedLaneCount`.
+pub struct LaneCount<const N: usize>;
 
-    fn tfar(&self, i: usize) -> f32 {
-        self.tfar[i]
-    }
-    fn set_tfar(&mut self, i: usize, far: f32) {
-        self.tfar[i] = far;
-    }
+/// Implemented for the packet widths supported by Embree, 4, 8 and 16,
+/// mapping the width onto the packet types and queries for that width.
+pub trait SupportedLaneCount: private::Sealed {
+    type Ray: SoARay + Copy;
+    type Hit: SoAHit + Copy;
+    type RayHit: SoARayHit<Ray = Self::Ray, Hit = Self::Hit>;
 
-    fn time(&self, i: usize) -> f32 {
-        self.time[i]
-    }
-    fn set_time(&mut self, i: usize, time: f32) {
-        self.time[i] = time;
-    }
+    /// Create a packet of rays with zero origin and direction and
+    /// an infinite extent
+    fn empty_ray() -> Self::Ray;
 
-    fn mask(&self, i: usize) -> u32 {
-        self.mask[i]
-    }
-    fn set_mask(&mut self, i: usize, mask: u32) {
-        self.mask[i] = mask;
-    }
+    #[doc(hidden)]
+    unsafe fn intersect(
+        valid: *const i32,
+        scene: RTCScene,
+        ctx: *mut RTCIntersectContext,
+        ray: *mut Self::RayHit,
+    );
+    #[doc(hidden)]
+    unsafe fn occluded(
+        valid: *const i32,
+        scene: RTCScene,
+        ctx: *mut RTCIntersectContext,
+        ray: *mut Self::Ray,
+    );
+}
 
-    fn id(&self, i: usize) -> u32 {
-        self.id[i]
-    }
-    fn set_id(&mut self, i: usize, id: u32) {
-        self.id[i] = id;
-    }
+mod private {
+    pub trait Sealed {}
+}
 
-    fn flags(&self, i: usize) -> u32 {
-        self.flags[i]
-    }
-    fn set_flags(&mut self, i: usize, flags: u32) {
-        self.flags[i] = flags;
-    }
+/// Accessors for the ray and hit packets of a combined ray and hit packet,
+/// for code generic over the packet width
+pub trait SoARayHit: Copy {
+    type Ray: SoARay + Copy;
+    type Hit: SoAHit + Copy;
+
+    fn new(ray: Self::Ray) -> Self;
+    fn ray(&self) -> &Self::Ray;
+    fn ray_mut(&mut self) -> &mut Self::Ray;
+    fn hit(&self) -> &Self::Hit;
+    fn hit_mut(&mut self) -> &mut Self::Hit;
 }
 
-impl Hit4 {
-    pub fn new() -> Hit4 {
-        sys::RTCHit4 {
-            Ng_x: [0.0; 4],
-            Ng_y: [0.0; 4],
-            Ng_z: [0.0; 4],
-            u: [0.0; 4],
-            v: [0.0; 4],
-            primID: [u32::MAX; 4],
-            geomID: [u32::MAX; 4],
-            instID: [[u32::MAX; 4]],
-        }
+/// The mask of which rays in a packet of `N` rays are active. It's aligned
+/// to the largest packet size, as Embree requires the mask to be aligned
+/// to the size of the packet.
+#[repr(C, align(64))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ValidMask<const N: usize> {
+    mask: [i32; N],
+}
+
+impl<const N: usize> ValidMask<N> {
+    /// A mask with all rays active
+    pub fn all() -> ValidMask<N> {
+        ValidMask { mask: [-1; N] }
+    }
+    /// A mask with no rays active
+    pub fn none() -> ValidMask<N> {
+        ValidMask { mask: [0; N] }
     }
-    pub fn any_hit(&self) -> bool {
-        self.hits().fold(false, |acc, g| acc || g)
+    pub fn is_valid(&self, i: usize) -> bool {
+        self.mask[i] != 0
     }
-    pub fn hits<'a>(&'a self) -> impl Iterator<Item = bool> + 'a {
-        self.geomID.iter().map(|g| *g != u32::MAX)
+    pub fn set_valid(&mut self, i: usize, valid: bool) {
+        self.mask[i] = if valid { -1 } else { 0 };
     }
-    pub fn iter(&self) -> SoAHitIter<Hit4> {
-        SoAHitIter::new(self, 4)
+    /// Get the mask in the representation used by Embree, -1 for
+    /// active rays and 0 for inactive rays
+    pub fn as_array(&self) -> &[i32; N] {
+        &self.mask
     }
-    pub fn iter_hits<'a>(&'a self) -> impl Iterator<Item = SoAHitRef<Hit4>> + 'a {
-        SoAHitIter::new(self, 4).filter(|h| h.hit())
+    pub fn count(&self) -> usize {
+        self.mask.iter().filter(|m| **m != 0).count()
     }
 }
 
-impl SoAHit for Hit4 {
-    fn normal(&self, i: usize) -> Vector3<f32> {
-        Vector3::new(self.Ng_x[i], self.Ng_y[i], self.Ng_z[i])
-    }
-    fn set_normal(&mut self, i: usize, n: Vector3<f32>) {
-        self.Ng_x[i] = n.x;
-        self.Ng_y[i] = n.y;
-        self.Ng_z[i] = n.z;
+impl<const N: usize> From<[bool; N]> for ValidMask<N> {
+    fn from(valid: [bool; N]) -> ValidMask<N> {
+        let mut mask = ValidMask::none();
+        for (i, v) in valid.iter().enumerate() {
+            mask.set_valid(i, *v);
+        }
+        mask
     }
+}
 
-    fn uv(&self, i: usize) -> (f32, f32) {
-        (self.u[i], self.v[i])
-    }
-    fn set_u(&mut self, i: usize, u: f32) {
-        self.u[i] = u;
-    }
-    fn set_v(&mut self, i: usize, v: f32) {
-        self.v[i] = v;
-    }
+macro_rules! ray_packet {
+    ($ray:ident, $hit:ident, $rayhit:ident, $n:expr, $intersect:ident, $occluded:ident) => {
+        impl $ray {
+            pub fn empty() -> $ray {
+                $ray::segment(
+                    [[0.0; 3]; $n],
+                    [[0.0; 3]; $n],
+                    [0.0; $n],
+                    [f32::INFINITY; $n],
+                )
+            }
+            pub fn new<O, D>(origin: [O; $n], dir: [D; $n]) -> $ray
+            where
+                O: Into<Vector3<f32>> + Copy,
+                D: Into<Vector3<f32>> + Copy,
+            {
+                $ray::segment(origin, dir, [0.0; $n], [f32::INFINITY; $n])
+            }
+            pub fn segment<O, D>(
+                origin: [O; $n],
+                dir: [D; $n],
+                tnear: [f32; $n],
+                tfar: [f32; $n],
+            ) -> $ray
+            where
+                O: Into<Vector3<f32>> + Copy,
+                D: Into<Vector3<f32>> + Copy,
+            {
+                let mut ray = $ray {
+                    org_x: [0.0; $n],
+                    org_y: [0.0; $n],
+                    org_z: [0.0; $n],
+                    dir_x: [0.0; $n],
+                    dir_y: [0.0; $n],
+                    dir_z: [0.0; $n],
+                    tnear: tnear,
+                    tfar: tfar,
+                    time: [0.0; $n],
+                    mask: [u32::MAX; $n],
+                    id: [0; $n],
+                    flags: [0; $n],
+                };
+                for i in 0..$n {
+                    ray.set_org(i, origin[i].into());
+                    ray.set_dir(i, dir[i].into());
+                }
+                ray
+            }
+            pub fn iter(&self) -> SoARayIter<'_, $ray> {
+                SoARayIter::new(self, $n)
+            }
+            pub fn iter_mut(&mut self) -> SoARayIterMut<'_, $ray> {
+                SoARayIterMut::new(self, $n)
+            }
+        }
 
-    fn prim_id(&self, i: usize) -> u32 {
-        self.primID[i]
-    }
-    fn set_prim_id(&mut self, i: usize, id: u32) {
-        self.primID[i] = id;
-    }
+        impl SoARay for $ray {
+            fn org(&self, i: usize) -> Vector3<f32> {
+                Vector3 {
+                    x: self.org_x[i],
+                    y: self.org_y[i],
+                    z: self.org_z[i],
+                }
+            }
+            fn set_org(&mut self, i: usize, o: Vector3<f32>) {
+                self.org_x[i] = o.x;
+                self.org_y[i] = o.y;
+                self.org_z[i] = o.z;
+            }
 
-    fn geom_id(&self, i: usize) -> u32 {
-        self.geomID[i]
-    }
-    fn set_geom_id(&mut self, i: usize, id: u32) {
-        self.geomID[i] = id;
-    }
+            fn dir(&self, i: usize) -> Vector3<f32> {
+                Vector3 {
+                    x: self.dir_x[i],
+                    y: self.dir_y[i],
+                    z: self.dir_z[i],
+                }
+            }
+            fn set_dir(&mut self, i: usize, d: Vector3<f32>) {
+                self.dir_x[i] = d.x;
+                self.dir_y[i] = d.y;
+                self.dir_z[i] = d.z;
+            }
 
-    fn inst_id(&self, i: usize) -> u32 {
-        self.instID[0][i]
-    }
-    fn set_inst_id(&mut self, i: usize, id: u32) {
-        self.instID[0][i] = id;
-    }
-}
+            fn tnear(&self, i: usize) -> f32 {
+                self.tnear[i]
+            }
+            fn set_tnear(&mut self, i: usize, near: f32) {
+                self.tnear[i] = near;
+            }
+
+            fn tfar(&self, i: usize) -> f32 {
+                self.tfar[i]
+            }
+            fn set_tfar(&mut self, i: usize, far: f32) {
+                self.tfar[i] = far;
+            }
 
-impl RayHit4 {
-    pub fn new(ray: Ray4) -> RayHit4 {
-        sys::RTCRayHit4 {
-            ray: ray,
-            hit: Hit4::new(),
+            fn time(&self, i: usize) -> f32 {
+                self.time[i]
+            }
+            fn set_time(&mut self, i: usize, time: f32) {
+                self.time[i] = time;
+            }
+
+            fn mask(&self, i: usize) -> u32 {
+                self.mask[i]
+            }
+            fn set_mask(&mut self, i: usize, mask: u32) {
+                self.mask[i] = mask;
+            }
+
+            fn id(&self, i: usize) -> u32 {
+                self.id[i]
+            }
+            fn set_id(&mut self, i: usize, id: u32) {
+                self.id[i] = id;
+            }
+
+            fn flags(&self, i: usize) -> u32 {
+                self.flags[i]
+            }
+            fn set_flags(&mut self, i: usize, flags: u32) {
+                self.flags[i] = flags;
+            }
         }
-    }
-    pub fn iter(&self) -> std::iter::Zip<SoARayIter<Ray4>, SoAHitIter<Hit4>> {
-        self.ray.iter().zip(self.hit.iter())
-    }
+
+        impl $hit {
+            pub fn new() -> $hit {
+                $hit {
+                    Ng_x: [0.0; $n],
+                    Ng_y: [0.0; $n],
+                    Ng_z: [0.0; $n],
+                    u: [0.0; $n],
+                    v: [0.0; $n],
+                    primID: [u32::MAX; $n],
+                    geomID: [u32::MAX; $n],
+                    instID: [[u32::MAX; $n]],
+                }
+            }
+            pub fn any_hit(&self) -> bool {
+                self.hits().fold(false, |acc, g| acc || g)
+            }
+            pub fn hits<'a>(&'a self) -> impl Iterator<Item = bool> + 'a {
+                self.geomID.iter().map(|g| *g != u32::MAX)
+            }
+            pub fn iter(&self) -> SoAHitIter<'_, $hit> {
+                SoAHitIter::new(self, $n)
+            }
+            pub fn iter_hits<'a>(&'a self) -> impl Iterator<Item = SoAHitRef<'a, $hit>> + 'a {
+                SoAHitIter::new(self, $n).filter(|h| h.hit())
+            }
+        }
+
+        impl SoAHit for $hit {
+            fn normal(&self, i: usize) -> Vector3<f32> {
+                Vector3 {
+                    x: self.Ng_x[i],
+                    y: self.Ng_y[i],
+                    z: self.Ng_z[i],
+                }
+            }
+            fn set_normal(&mut self, i: usize, n: Vector3<f32>) {
+                self.Ng_x[i] = n.x;
+                self.Ng_y[i] = n.y;
+                self.Ng_z[i] = n.z;
+            }
+
+            fn uv(&self, i: usize) -> (f32, f32) {
+                (self.u[i], self.v[i])
+            }
+            fn set_u(&mut self, i: usize, u: f32) {
+                self.u[i] = u;
+            }
+            fn set_v(&mut self, i: usize, v: f32) {
+                self.v[i] = v;
+            }
+
+            fn prim_id(&self, i: usize) -> u32 {
+                self.primID[i]
+            }
+            fn set_prim_id(&mut self, i: usize, id: u32) {
+                self.primID[i] = id;
+            }
+
+            fn geom_id(&self, i: usize) -> u32 {
+                self.geomID[i]
+            }
+            fn set_geom_id(&mut self, i: usize, id: u32) {
+                self.geomID[i] = id;
+            }
+
+            fn inst_id(&self, i: usize) -> u32 {
+                self.instID[0][i]
+            }
+            fn set_inst_id(&mut self, i: usize, id: u32) {
+                self.instID[0][i] = id;
+            }
+        }
+
+        impl $rayhit {
+            pub fn new(ray: $ray) -> $rayhit {
+                $rayhit {
+                    ray: ray,
+                    hit: $hit::new(),
+                }
+            }
+            pub fn iter(&self) -> std::iter::Zip<SoARayIter<'_, $ray>, SoAHitIter<'_, $hit>> {
+                self.ray.iter().zip(self.hit.iter())
+            }
+        }
+
+        impl SoARayHit for $rayhit {
+            type Ray = $ray;
+            type Hit = $hit;
+
+            fn new(ray: $ray) -> $rayhit {
+                $rayhit::new(ray)
+            }
+            fn ray(&self) -> &$ray {
+                &self.ray
+            }
+            fn ray_mut(&mut self) -> &mut $ray {
+                &mut self.ray
+            }
+            fn hit(&self) -> &$hit {
+                &self.hit
+            }
+            fn hit_mut(&mut self) -> &mut $hit {
+                &mut self.hit
+            }
+        }
+
+        impl private::Sealed for LaneCount<$n> {}
+
+        impl SupportedLaneCount for LaneCount<$n> {
+            type Ray = $ray;
+            type Hit = $hit;
+            type RayHit = $rayhit;
+
+            fn empty_ray() -> $ray {
+                $ray::empty()
+            }
+            unsafe fn intersect(
+                valid: *const i32,
+                scene: RTCScene,
+                ctx: *mut RTCIntersectContext,
+                ray: *mut $rayhit,
+            ) {
+                sys::$intersect(valid, scene, ctx, ray);
+            }
+            unsafe fn occluded(
+                valid: *const i32,
+                scene: RTCScene,
+                ctx: *mut RTCIntersectContext,
+                ray: *mut $ray,
+            ) {
+                sys::$occluded(valid, scene, ctx, ray);
+            }
+        }
+    };
+}
+
+ray_packet!(Ray4, Hit4, RayHit4, 4, rtcIntersect4, rtcOccluded4);
+ray_packet!(Ray8, Hit8, RayHit8, 8, rtcIntersect8, rtcOccluded8);
+ray_packet!(Ray16, Hit16, RayHit16, 16, rtcIntersect16, rtcOccluded16);
+
+#[test]
+fn test_valid_mask() {
+    let mut mask = ValidMask::from([true, false, true, false]);
+    assert_eq!(mask.as_array(), &[-1, 0, -1, 0]);
+    mask.set_valid(1, true);
+    assert!(mask.is_valid(1) && !mask.is_valid(3));
+    assert_eq!(mask.count(), 3);
+    assert_eq!(ValidMask::<8>::all().count(), 8);
+    assert_eq!(::std::mem::align_of::<ValidMask<4>>(), 64);
+}
+
+#[test]
+fn test_generic_packet() {
+    fn packet<const N: usize>(tfar: f32) -> RayHitPacket<N>
+    where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        let mut ray = LaneCount::<N>::empty_ray();
+        ray.set_tfar(N - 1, tfar);
+        SoARayHit::new(ray)
+    }
+    let p: RayHit8 = packet::<8>(2.0);
+    assert_eq!(p.ray.tfar(7), 2.0);
+    assert!(!p.hit.any_hit());
+    let p = packet::<16>(2.0);
+    assert_eq!(p.ray().tfar(15), 2.0);
 }
@@ -1,47 +1,160 @@
-use cgmath::Vector3;
+use mint::Vector3;
 use std::iter::Iterator;
 use std::marker::PhantomData;
 use std::{f32, u32};
 
+use aligned_vec::AlignedVec;
+use ray::{Hit, Ray};
 use soa_ray::{
     SoAHit, SoAHitIter, SoAHitIterMut, SoAHitRef, SoARay, SoARayIter, SoARayIterMut, SoARayRef,
     SoARayRefMut,
 };
 use sys;
-use {aligned_vector, aligned_vector_init};
+
+macro_rules! soa_ray_impl {
+    ($($lt:lifetime),* ; $t:ty) => {
+        impl<$($lt),*> SoARay for $t {
+            fn org(&self, i: usize) -> Vector3<f32> {
+                Vector3 { x: self.org_x[i], y: self.org_y[i], z: self.org_z[i] }
+            }
+            fn set_org(&mut self, i: usize, o: Vector3<f32>) {
+                self.org_x[i] = o.x;
+                self.org_y[i] = o.y;
+                self.org_z[i] = o.z;
+            }
+
+            fn dir(&self, i: usize) -> Vector3<f32> {
+                Vector3 { x: self.dir_x[i], y: self.dir_y[i], z: self.dir_z[i] }
+            }
+            fn set_dir(&mut self, i: usize, d: Vector3<f32>) {
+                self.dir_x[i] = d.x;
+                self.dir_y[i] = d.y;
+                self.dir_z[i] = d.z;
+            }
+
+            fn tnear(&self, i: usize) -> f32 {
+                self.tnear[i]
+            }
+            fn set_tnear(&mut self, i: usize, near: f32) {
+                self.tnear[i] = near;
+            }
+
+            fn tfar(&self, i: usize) -> f32 {
+                self.tfar[i]
+            }
+            fn set_tfar(&mut self, i: usize, far: f32) {
+                self.tfar[i] = far;
+            }
+
+            fn time(&self, i: usize) -> f32 {
+                self.time[i]
+            }
+            fn set_time(&mut self, i: usize, time: f32) {
+                self.time[i] = time;
+            }
+
+            fn mask(&self, i: usize) -> u32 {
+                self.mask[i]
+            }
+            fn set_mask(&mut self, i: usize, mask: u32) {
+                self.mask[i] = mask;
+            }
+
+            fn id(&self, i: usize) -> u32 {
+                self.id[i]
+            }
+            fn set_id(&mut self, i: usize, id: u32) {
+                self.id[i] = id;
+            }
+
+            fn flags(&self, i: usize) -> u32 {
+                self.flags[i]
+            }
+            fn set_flags(&mut self, i: usize, flags: u32) {
+                self.flags[i] = flags;
+            }
+        }
+    };
+}
+
+macro_rules! soa_hit_impl {
+    ($($lt:lifetime),* ; $t:ty) => {
+        impl<$($lt),*> SoAHit for $t {
+            fn normal(&self, i: usize) -> Vector3<f32> {
+                Vector3 { x: self.ng_x[i], y: self.ng_y[i], z: self.ng_z[i] }
+            }
+            fn set_normal(&mut self, i: usize, n: Vector3<f32>) {
+                self.ng_x[i] = n.x;
+                self.ng_y[i] = n.y;
+                self.ng_z[i] = n.z;
+            }
+
+            fn uv(&self, i: usize) -> (f32, f32) {
+                (self.u[i], self.v[i])
+            }
+            fn set_u(&mut self, i: usize, u: f32) {
+                self.u[i] = u;
+            }
+            fn set_v(&mut self, i: usize, v: f32) {
+                self.v[i] = v;
+            }
+
+            fn prim_id(&self, i: usize) -> u32 {
+                self.prim_id[i]
+            }
+            fn set_prim_id(&mut self, i: usize, id: u32) {
+                self.prim_id[i] = id;
+            }
+
+            fn geom_id(&self, i: usize) -> u32 {
+                self.geom_id[i]
+            }
+            fn set_geom_id(&mut self, i: usize, id: u32) {
+                self.geom_id[i] = id;
+            }
+
+            fn inst_id(&self, i: usize) -> u32 {
+                self.inst_id[i]
+            }
+            fn set_inst_id(&mut self, i: usize, id: u32) {
+                self.inst_id[i] = id;
+            }
+        }
+    };
+}
 
 /// A ray stream stored in SoA format
 pub struct RayN {
-    org_x: Vec<f32>,
-    org_y: Vec<f32>,
-    org_z: Vec<f32>,
-    tnear: Vec<f32>,
-    dir_x: Vec<f32>,
-    dir_y: Vec<f32>,
-    dir_z: Vec<f32>,
-    time: Vec<f32>,
-    tfar: Vec<f32>,
-    mask: Vec<::std::os::raw::c_uint>,
-    id: Vec<::std::os::raw::c_uint>,
-    flags: Vec<::std::os::raw::c_uint>,
+    org_x: AlignedVec<f32>,
+    org_y: AlignedVec<f32>,
+    org_z: AlignedVec<f32>,
+    tnear: AlignedVec<f32>,
+    dir_x: AlignedVec<f32>,
+    dir_y: AlignedVec<f32>,
+    dir_z: AlignedVec<f32>,
+    time: AlignedVec<f32>,
+    tfar: AlignedVec<f32>,
+    mask: AlignedVec<::std::os::raw::c_uint>,
+    id: AlignedVec<::std::os::raw::c_uint>,
+    flags: AlignedVec<::std::os::raw::c_uint>,
 }
 
 impl RayN {
     /// Allocate a new Ray stream with room for `n` rays
     pub fn new(n: usize) -> RayN {
         RayN {
-            org_x: aligned_vector::<f32>(n, 16),
-            org_y: aligned_vector::<f32>(n, 16),
-            org_z: aligned_vector::<f32>(n, 16),
-            tnear: aligned_vector_init::<f32>(n, 16, 0.0),
-            dir_x: aligned_vector::<f32>(n, 16),
-            dir_y: aligned_vector::<f32>(n, 16),
-            dir_z: aligned_vector::<f32>(n, 16),
-            time: aligned_vector_init::<f32>(n, 16, 0.0),
-            tfar: aligned_vector_init::<f32>(n, 16, f32::INFINITY),
-            mask: aligned_vector_init::<u32>(n, 16, u32::MAX),
-            id: aligned_vector_init::<u32>(n, 16, 0),
-            flags: aligned_vector_init::<u32>(n, 16, 0),
+            org_x: AlignedVec::from_elem(0.0, n),
+            org_y: AlignedVec::from_elem(0.0, n),
+            org_z: AlignedVec::from_elem(0.0, n),
+            tnear: AlignedVec::from_elem(0.0, n),
+            dir_x: AlignedVec::from_elem(0.0, n),
+            dir_y: AlignedVec::from_elem(0.0, n),
+            dir_z: AlignedVec::from_elem(0.0, n),
+            time: AlignedVec::from_elem(0.0, n),
+            tfar: AlignedVec::from_elem(f32::INFINITY, n),
+            mask: AlignedVec::from_elem(u32::MAX, n),
+            id: AlignedVec::from_elem(0, n),
+            flags: AlignedVec::from_elem(0, n),
         }
     }
     pub fn iter(&self) -> SoARayIter<RayN> {
@@ -54,7 +167,74 @@ impl RayN {
     pub fn len(&self) -> usize {
         self.org_x.len()
     }
-    pub unsafe fn as_raynp(&mut self) -> sys::RTCRayNp {
+    /// Resize the stream to `n` rays, e.g. to reuse it between bounces.
+    /// New rays have a zero origin and direction and an infinite extent.
+    pub fn resize(&mut self, n: usize) {
+        self.org_x.resize(n, 0.0);
+        self.org_y.resize(n, 0.0);
+        self.org_z.resize(n, 0.0);
+        self.tnear.resize(n, 0.0);
+        self.dir_x.resize(n, 0.0);
+        self.dir_y.resize(n, 0.0);
+        self.dir_z.resize(n, 0.0);
+        self.time.resize(n, 0.0);
+        self.tfar.resize(n, f32::INFINITY);
+        self.mask.resize(n, u32::MAX);
+        self.id.resize(n, 0);
+        self.flags.resize(n, 0);
+    }
+    /// Remove all rays from the stream, keeping its allocation
+    pub fn clear(&mut self) {
+        self.resize(0);
+    }
+    /// Append a ray to the stream
+    pub fn push(&mut self, ray: Ray) {
+        self.org_x.push(ray.org_x);
+        self.org_y.push(ray.org_y);
+        self.org_z.push(ray.org_z);
+        self.tnear.push(ray.tnear);
+        self.dir_x.push(ray.dir_x);
+        self.dir_y.push(ray.dir_y);
+        self.dir_z.push(ray.dir_z);
+        self.time.push(ray.time);
+        self.tfar.push(ray.tfar);
+        self.mask.push(ray.mask);
+        self.id.push(ray.id);
+        self.flags.push(ray.flags);
+    }
+    /// Copy ray `from` into ray `to`
+    fn move_ray(&mut self, from: usize, to: usize) {
+        self.org_x[to] = self.org_x[from];
+        self.org_y[to] = self.org_y[from];
+        self.org_z[to] = self.org_z[from];
+        self.tnear[to] = self.tnear[from];
+        self.dir_x[to] = self.dir_x[from];
+        self.dir_y[to] = self.dir_y[from];
+        self.dir_z[to] = self.dir_z[from];
+        self.time[to] = self.time[from];
+        self.tfar[to] = self.tfar[from];
+        self.mask[to] = self.mask[from];
+        self.id[to] = self.id[from];
+        self.flags[to] = self.flags[from];
+    }
+    /// Borrow the stream as a `RayNpRef`
+    pub fn np_ref(&mut self) -> RayNpRef<'_> {
+        RayNpRef {
+            org_x: &mut self.org_x,
+            org_y: &mut self.org_y,
+            org_z: &mut self.org_z,
+            tnear: &mut self.tnear,
+            dir_x: &mut self.dir_x,
+            dir_y: &mut self.dir_y,
+            dir_z: &mut self.dir_z,
+            time: &mut self.time,
+            tfar: &mut self.tfar,
+            mask: &mut self.mask,
+            id: &mut self.id,
+            flags: &mut self.flags,
+        }
+    }
+    pub fn as_raynp(&mut self) -> sys::RTCRayNp {
         sys::RTCRayNp {
             org_x: self.org_x.as_mut_ptr(),
             org_y: self.org_y.as_mut_ptr(),
@@ -72,90 +252,31 @@ impl RayN {
     }
 }
 
-impl SoARay for RayN {
-    fn org(&self, i: usize) -> Vector3<f32> {
-        Vector3::new(self.org_x[i], self.org_y[i], self.org_z[i])
-    }
-    fn set_org(&mut self, i: usize, o: Vector3<f32>) {
-        self.org_x[i] = o.x;
-        self.org_y[i] = o.y;
-        self.org_z[i] = o.z;
-    }
-
-    fn dir(&self, i: usize) -> Vector3<f32> {
-        Vector3::new(self.dir_x[i], self.dir_y[i], self.dir_z[i])
-    }
-    fn set_dir(&mut self, i: usize, d: Vector3<f32>) {
-        self.dir_x[i] = d.x;
-        self.dir_y[i] = d.y;
-        self.dir_z[i] = d.z;
-    }
-
-    fn tnear(&self, i: usize) -> f32 {
-        self.tnear[i]
-    }
-    fn set_tnear(&mut self, i: usize, near: f32) {
-        self.tnear[i] = near;
-    }
-
-    fn tfar(&self, i: usize) -> f32 {
-        self.tfar[i]
-    }
-    fn set_tfar(&mut self, i: usize, far: f32) {
-        self.tfar[i] = far;
-    }
-
-    fn time(&self, i: usize) -> f32 {
-        self.time[i]
-    }
-    fn set_time(&mut self, i: usize, time: f32) {
-        self.time[i] = time;
-    }
-
-    fn mask(&self, i: usize) -> u32 {
-        self.mask[i]
-    }
-    fn set_mask(&mut self, i: usize, mask: u32) {
-        self.mask[i] = mask;
-    }
-
-    fn id(&self, i: usize) -> u32 {
-        self.id[i]
-    }
-    fn set_id(&mut self, i: usize, id: u32) {
-        self.id[i] = id;
-    }
-
-    fn flags(&self, i: usize) -> u32 {
-        self.flags[i]
-    }
-    fn set_flags(&mut self, i: usize, flags: u32) {
-        self.flags[i] = flags;
-    }
-}
+soa_ray_impl!(; RayN);
+soa_ray_impl!('a; RayNpRef<'a>);
 
 pub struct HitN {
-    ng_x: Vec<f32>,
-    ng_y: Vec<f32>,
-    ng_z: Vec<f32>,
-    u: Vec<f32>,
-    v: Vec<f32>,
-    prim_id: Vec<::std::os::raw::c_uint>,
-    geom_id: Vec<::std::os::raw::c_uint>,
-    inst_id: Vec<::std::os::raw::c_uint>,
+    ng_x: AlignedVec<f32>,
+    ng_y: AlignedVec<f32>,
+    ng_z: AlignedVec<f32>,
+    u: AlignedVec<f32>,
+    v: AlignedVec<f32>,
+    prim_id: AlignedVec<::std::os::raw::c_uint>,
+    geom_id: AlignedVec<::std::os::raw::c_uint>,
+    inst_id: AlignedVec<::std::os::raw::c_uint>,
 }
 
 impl HitN {
     pub fn new(n: usize) -> HitN {
         HitN {
-            ng_x: aligned_vector::<f32>(n, 16),
-            ng_y: aligned_vector::<f32>(n, 16),
-            ng_z: aligned_vector::<f32>(n, 16),
-            u: aligned_vector::<f32>(n, 16),
-            v: aligned_vector::<f32>(n, 16),
-            prim_id: aligned_vector_init::<u32>(n, 16, u32::MAX),
-            geom_id: aligned_vector_init::<u32>(n, 16, u32::MAX),
-            inst_id: aligned_vector_init::<u32>(n, 16, u32::MAX),
+            ng_x: AlignedVec::from_elem(0.0, n),
+            ng_y: AlignedVec::from_elem(0.0, n),
+            ng_z: AlignedVec::from_elem(0.0, n),
+            u: AlignedVec::from_elem(0.0, n),
+            v: AlignedVec::from_elem(0.0, n),
+            prim_id: AlignedVec::from_elem(u32::MAX, n),
+            geom_id: AlignedVec::from_elem(u32::MAX, n),
+            inst_id: AlignedVec::from_elem(u32::MAX, n),
         }
     }
     pub fn any_hit(&self) -> bool {
@@ -173,7 +294,76 @@ impl HitN {
     pub fn len(&self) -> usize {
         self.ng_x.len()
     }
-    pub unsafe fn as_hitnp(&mut self) -> sys::RTCHitNp {
+    /// Resize the stream to `n` hits, new hits are misses
+    pub fn resize(&mut self, n: usize) {
+        self.ng_x.resize(n, 0.0);
+        self.ng_y.resize(n, 0.0);
+        self.ng_z.resize(n, 0.0);
+        self.u.resize(n, 0.0);
+        self.v.resize(n, 0.0);
+        self.prim_id.resize(n, u32::MAX);
+        self.geom_id.resize(n, u32::MAX);
+        self.inst_id.resize(n, u32::MAX);
+    }
+    /// Remove all hits from the stream, keeping its allocation
+    pub fn clear(&mut self) {
+        self.resize(0);
+    }
+    /// Append a hit to the stream
+    pub fn push(&mut self, hit: Hit) {
+        self.ng_x.push(hit.Ng_x);
+        self.ng_y.push(hit.Ng_y);
+        self.ng_z.push(hit.Ng_z);
+        self.u.push(hit.u);
+        self.v.push(hit.v);
+        self.prim_id.push(hit.primID);
+        self.geom_id.push(hit.geomID);
+        self.inst_id.push(hit.instID[0]);
+    }
+    /// Reset all hits in the stream to misses, e.g. before tracing the
+    /// next bounce
+    pub fn reset(&mut self) {
+        for x in self.ng_x.iter_mut().chain(self.ng_y.iter_mut()) {
+            *x = 0.0;
+        }
+        for x in self.ng_z.iter_mut() {
+            *x = 0.0;
+        }
+        for x in self.u.iter_mut().chain(self.v.iter_mut()) {
+            *x = 0.0;
+        }
+        for id in self.prim_id.iter_mut().chain(self.geom_id.iter_mut()) {
+            *id = u32::MAX;
+        }
+        for id in self.inst_id.iter_mut() {
+            *id = u32::MAX;
+        }
+    }
+    /// Copy hit `from` into hit `to`
+    fn move_hit(&mut self, from: usize, to: usize) {
+        self.ng_x[to] = self.ng_x[from];
+        self.ng_y[to] = self.ng_y[from];
+        self.ng_z[to] = self.ng_z[from];
+        self.u[to] = self.u[from];
+        self.v[to] = self.v[from];
+        self.prim_id[to] = self.prim_id[from];
+        self.geom_id[to] = self.geom_id[from];
+        self.inst_id[to] = self.inst_id[from];
+    }
+    /// Borrow the stream as a `HitNpRef`
+    pub fn np_ref(&mut self) -> HitNpRef<'_> {
+        HitNpRef {
+            ng_x: &mut self.ng_x,
+            ng_y: &mut self.ng_y,
+            ng_z: &mut self.ng_z,
+            u: &mut self.u,
+            v: &mut self.v,
+            prim_id: &mut self.prim_id,
+            geom_id: &mut self.geom_id,
+            inst_id: &mut self.inst_id,
+        }
+    }
+    pub fn as_hitnp(&mut self) -> sys::RTCHitNp {
         sys::RTCHitNp {
             Ng_x: self.ng_x.as_mut_ptr(),
             Ng_y: self.ng_y.as_mut_ptr(),
@@ -187,71 +377,396 @@ impl HitN {
     }
 }
 
-impl SoAHit for HitN {
-    fn normal(&self, i: usize) -> Vector3<f32> {
-        Vector3::new(self.ng_x[i], self.ng_y[i], self.ng_z[i])
-    }
-    fn set_normal(&mut self, i: usize, n: Vector3<f32>) {
-        self.ng_x[i] = n.x;
-        self.ng_y[i] = n.y;
-        self.ng_z[i] = n.z;
-    }
+soa_hit_impl!(; HitN);
+soa_hit_impl!('a; HitNpRef<'a>);
 
-    fn uv(&self, i: usize) -> (f32, f32) {
-        (self.u[i], self.v[i])
+pub struct RayHitN {
+    pub ray: RayN,
+    pub hit: HitN,
+}
+
+impl RayHitN {
+    pub fn new(ray: RayN) -> RayHitN {
+        let n = ray.len();
+        RayHitN {
+            ray: ray,
+            hit: HitN::new(n),
+        }
     }
-    fn set_u(&mut self, i: usize, u: f32) {
-        self.u[i] = u;
+    pub fn iter(&self) -> std::iter::Zip<SoARayIter<RayN>, SoAHitIter<HitN>> {
+        self.ray.iter().zip(self.hit.iter())
     }
-    fn set_v(&mut self, i: usize, v: f32) {
-        self.v[i] = v;
+    pub fn len(&self) -> usize {
+        self.ray.len()
     }
-
-    fn prim_id(&self, i: usize) -> u32 {
-        self.prim_id[i]
+    /// Resize the stream to `n` rays, see `RayN::resize` and `HitN::resize`
+    pub fn resize(&mut self, n: usize) {
+        self.ray.resize(n);
+        self.hit.resize(n);
+    }
+    /// Remove all rays and hits from the stream, keeping its allocation
+    pub fn clear(&mut self) {
+        self.ray.clear();
+        self.hit.clear();
+    }
+    /// Append a ray to the stream, with its hit set to a miss
+    pub fn push(&mut self, ray: Ray) {
+        self.ray.push(ray);
+        self.hit.push(Hit::new());
+    }
+    /// Reset all hits in the stream to misses, see `HitN::reset`
+    pub fn reset_hits(&mut self) {
+        self.hit.reset();
+    }
+    /// Remove the rays for which `keep` returns `false`, e.g. terminated
+    /// paths, preserving the order of the remaining rays. Returns the
+    /// index remap, where entry `i` is the index ray `i` had before compaction.
+    pub fn compact<F>(&mut self, mut keep: F) -> Vec<usize>
+    where
+        F: FnMut(SoARayRef<RayN>, SoAHitRef<HitN>) -> bool,
+    {
+        let mut remap = Vec::new();
+        for i in 0..self.len() {
+            if keep(SoARayRef::new(&self.ray, i), SoAHitRef::new(&self.hit, i)) {
+                let j = remap.len();
+                if i != j {
+                    self.ray.move_ray(i, j);
+                    self.hit.move_hit(i, j);
+                }
+                remap.push(i);
+            }
+        }
+        self.resize(remap.len());
+        remap
+    }
+    /// Borrow the stream as a `RayHitNpRef`
+    pub fn np_ref(&mut self) -> RayHitNpRef<'_> {
+        RayHitNpRef {
+            ray: self.ray.np_ref(),
+            hit: self.hit.np_ref(),
+        }
     }
-    fn set_prim_id(&mut self, i: usize, id: u32) {
-        self.prim_id[i] = id;
+    pub fn as_rayhitnp(&mut self) -> sys::RTCRayHitNp {
+        sys::RTCRayHitNp {
+            ray: self.ray.as_raynp(),
+            hit: self.hit.as_hitnp(),
+        }
     }
+}
+
+/// A borrowed ray stream in SoA format, e.g. over the ray queues of a
+/// wavefront renderer, so they can be traced without copying them into a
+/// `RayN`. The slices must all have the same length and be aligned to 16 bytes.
+pub struct RayNpRef<'a> {
+    pub org_x: &'a mut [f32],
+    pub org_y: &'a mut [f32],
+    pub org_z: &'a mut [f32],
+    pub tnear: &'a mut [f32],
+    pub dir_x: &'a mut [f32],
+    pub dir_y: &'a mut [f32],
+    pub dir_z: &'a mut [f32],
+    pub time: &'a mut [f32],
+    pub tfar: &'a mut [f32],
+    pub mask: &'a mut [::std::os::raw::c_uint],
+    pub id: &'a mut [::std::os::raw::c_uint],
+    pub flags: &'a mut [::std::os::raw::c_uint],
+}
 
-    fn geom_id(&self, i: usize) -> u32 {
-        self.geom_id[i]
+impl<'a> RayNpRef<'a> {
+    /// Split the stream into the rays before and after `mid`, e.g. to trace
+    /// the parts on different threads. `mid` should be a multiple of 4 to keep
+    /// both parts aligned to 16 bytes.
+    pub fn split_at_mut(self, mid: usize) -> (RayNpRef<'a>, RayNpRef<'a>) {
+        let (org_x0, org_x1) = self.org_x.split_at_mut(mid);
+        let (org_y0, org_y1) = self.org_y.split_at_mut(mid);
+        let (org_z0, org_z1) = self.org_z.split_at_mut(mid);
+        let (tnear0, tnear1) = self.tnear.split_at_mut(mid);
+        let (dir_x0, dir_x1) = self.dir_x.split_at_mut(mid);
+        let (dir_y0, dir_y1) = self.dir_y.split_at_mut(mid);
+        let (dir_z0, dir_z1) = self.dir_z.split_at_mut(mid);
+        let (time0, time1) = self.time.split_at_mut(mid);
+        let (tfar0, tfar1) = self.tfar.split_at_mut(mid);
+        let (mask0, mask1) = self.mask.split_at_mut(mid);
+        let (id0, id1) = self.id.split_at_mut(mid);
+        let (flags0, flags1) = self.flags.split_at_mut(mid);
+        (
+            RayNpRef {
+                org_x: org_x0,
+                org_y: org_y0,
+                org_z: org_z0,
+                tnear: tnear0,
+                dir_x: dir_x0,
+                dir_y: dir_y0,
+                dir_z: dir_z0,
+                time: time0,
+                tfar: tfar0,
+                mask: mask0,
+                id: id0,
+                flags: flags0,
+            },
+            RayNpRef {
+                org_x: org_x1,
+                org_y: org_y1,
+                org_z: org_z1,
+                tnear: tnear1,
+                dir_x: dir_x1,
+                dir_y: dir_y1,
+                dir_z: dir_z1,
+                time: time1,
+                tfar: tfar1,
+                mask: mask1,
+                id: id1,
+                flags: flags1,
+            },
+        )
+    }
+    pub fn iter(&self) -> SoARayIter<'_, RayNpRef<'a>> {
+        SoARayIter::new(self, self.len())
     }
-    fn set_geom_id(&mut self, i: usize, id: u32) {
-        self.geom_id[i] = id;
+    pub fn len(&self) -> usize {
+        self.org_x.len()
     }
-
-    fn inst_id(&self, i: usize) -> u32 {
-        self.inst_id[i]
+    pub fn is_empty(&self) -> bool {
+        self.org_x.is_empty()
     }
-    fn set_inst_id(&mut self, i: usize, id: u32) {
-        self.inst_id[i] = id;
+    /// Get the pointers to the stream passed to Embree, panics if the slices
+    /// don't all have the same length or aren't aligned to 16 bytes.
+    pub fn as_raynp(&mut self) -> sys::RTCRayNp {
+        let n = self.len();
+        check_soa_slice(self.org_x, n);
+        check_soa_slice(self.org_y, n);
+        check_soa_slice(self.org_z, n);
+        check_soa_slice(self.tnear, n);
+        check_soa_slice(self.dir_x, n);
+        check_soa_slice(self.dir_y, n);
+        check_soa_slice(self.dir_z, n);
+        check_soa_slice(self.time, n);
+        check_soa_slice(self.tfar, n);
+        check_soa_slice(self.mask, n);
+        check_soa_slice(self.id, n);
+        check_soa_slice(self.flags, n);
+        sys::RTCRayNp {
+            org_x: self.org_x.as_mut_ptr(),
+            org_y: self.org_y.as_mut_ptr(),
+            org_z: self.org_z.as_mut_ptr(),
+            dir_x: self.dir_x.as_mut_ptr(),
+            dir_y: self.dir_y.as_mut_ptr(),
+            dir_z: self.dir_z.as_mut_ptr(),
+            tnear: self.tnear.as_mut_ptr(),
+            tfar: self.tfar.as_mut_ptr(),
+            time: self.time.as_mut_ptr(),
+            mask: self.mask.as_mut_ptr(),
+            id: self.id.as_mut_ptr(),
+            flags: self.flags.as_mut_ptr(),
+        }
     }
 }
 
-pub struct RayHitN {
-    pub ray: RayN,
-    pub hit: HitN,
+/// A borrowed hit stream in SoA format, see `RayNpRef`
+pub struct HitNpRef<'a> {
+    pub ng_x: &'a mut [f32],
+    pub ng_y: &'a mut [f32],
+    pub ng_z: &'a mut [f32],
+    pub u: &'a mut [f32],
+    pub v: &'a mut [f32],
+    pub prim_id: &'a mut [::std::os::raw::c_uint],
+    pub geom_id: &'a mut [::std::os::raw::c_uint],
+    pub inst_id: &'a mut [::std::os::raw::c_uint],
 }
 
-impl RayHitN {
-    pub fn new(ray: RayN) -> RayHitN {
-        let n = ray.len();
-        RayHitN {
-            ray: ray,
-            hit: HitN::new(n),
+impl<'a> HitNpRef<'a> {
+    /// Split the stream into the hits before and after `mid`, e.g. to trace
+    /// the parts on different threads. `mid` should be a multiple of 4 to keep
+    /// both parts aligned to 16 bytes.
+    pub fn split_at_mut(self, mid: usize) -> (HitNpRef<'a>, HitNpRef<'a>) {
+        let (ng_x0, ng_x1) = self.ng_x.split_at_mut(mid);
+        let (ng_y0, ng_y1) = self.ng_y.split_at_mut(mid);
+        let (ng_z0, ng_z1) = self.ng_z.split_at_mut(mid);
+        let (u0, u1) = self.u.split_at_mut(mid);
+        let (v0, v1) = self.v.split_at_mut(mid);
+        let (prim_id0, prim_id1) = self.prim_id.split_at_mut(mid);
+        let (geom_id0, geom_id1) = self.geom_id.split_at_mut(mid);
+        let (inst_id0, inst_id1) = self.inst_id.split_at_mut(mid);
+        (
+            HitNpRef {
+                ng_x: ng_x0,
+                ng_y: ng_y0,
+                ng_z: ng_z0,
+                u: u0,
+                v: v0,
+                prim_id: prim_id0,
+                geom_id: geom_id0,
+                inst_id: inst_id0,
+            },
+            HitNpRef {
+                ng_x: ng_x1,
+                ng_y: ng_y1,
+                ng_z: ng_z1,
+                u: u1,
+                v: v1,
+                prim_id: prim_id1,
+                geom_id: geom_id1,
+                inst_id: inst_id1,
+            },
+        )
+    }
+    pub fn iter(&self) -> SoAHitIter<'_, HitNpRef<'a>> {
+        SoAHitIter::new(self, self.len())
+    }
+    pub fn len(&self) -> usize {
+        self.ng_x.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ng_x.is_empty()
+    }
+    /// Get the pointers to the stream passed to Embree, panics if the slices
+    /// don't all have the same length or aren't aligned to 16 bytes.
+    pub fn as_hitnp(&mut self) -> sys::RTCHitNp {
+        let n = self.len();
+        check_soa_slice(self.ng_x, n);
+        check_soa_slice(self.ng_y, n);
+        check_soa_slice(self.ng_z, n);
+        check_soa_slice(self.u, n);
+        check_soa_slice(self.v, n);
+        check_soa_slice(self.prim_id, n);
+        check_soa_slice(self.geom_id, n);
+        check_soa_slice(self.inst_id, n);
+        sys::RTCHitNp {
+            Ng_x: self.ng_x.as_mut_ptr(),
+            Ng_y: self.ng_y.as_mut_ptr(),
+            Ng_z: self.ng_z.as_mut_ptr(),
+            u: self.u.as_mut_ptr(),
+            v: self.v.as_mut_ptr(),
+            primID: self.prim_id.as_mut_ptr(),
+            geomID: self.geom_id.as_mut_ptr(),
+            instID: [self.inst_id.as_mut_ptr(); 1usize],
         }
     }
-    pub fn iter(&self) -> std::iter::Zip<SoARayIter<RayN>, SoAHitIter<HitN>> {
+}
+
+/// A borrowed ray and hit stream in SoA format, see `RayNpRef`
+pub struct RayHitNpRef<'a> {
+    pub ray: RayNpRef<'a>,
+    pub hit: HitNpRef<'a>,
+}
+
+impl<'a> RayHitNpRef<'a> {
+    /// Split the stream at `mid`, see `RayNpRef::split_at_mut`
+    pub fn split_at_mut(self, mid: usize) -> (RayHitNpRef<'a>, RayHitNpRef<'a>) {
+        let (ray0, ray1) = self.ray.split_at_mut(mid);
+        let (hit0, hit1) = self.hit.split_at_mut(mid);
+        (
+            RayHitNpRef {
+                ray: ray0,
+                hit: hit0,
+            },
+            RayHitNpRef {
+                ray: ray1,
+                hit: hit1,
+            },
+        )
+    }
+    pub fn iter(
+        &self,
+    ) -> std::iter::Zip<SoARayIter<'_, RayNpRef<'a>>, SoAHitIter<'_, HitNpRef<'a>>> {
         self.ray.iter().zip(self.hit.iter())
     }
     pub fn len(&self) -> usize {
         self.ray.len()
     }
-    pub unsafe fn as_rayhitnp(&mut self) -> sys::RTCRayHitNp {
+    pub fn is_empty(&self) -> bool {
+        self.ray.is_empty()
+    }
+    /// Get the pointers to the stream passed to Embree, panics if the ray
+    /// and hit slices don't all have the same length or aren't aligned to 16 bytes.
+    pub fn as_rayhitnp(&mut self) -> sys::RTCRayHitNp {
+        assert_eq!(self.ray.len(), self.hit.len());
         sys::RTCRayHitNp {
             ray: self.ray.as_raynp(),
             hit: self.hit.as_hitnp(),
         }
     }
 }
+
+fn check_soa_slice<T>(s: &[T], n: usize) {
+    assert_eq!(s.len(), n, "SoA stream slices must have the same length");
+    assert!(
+        n == 0 || s.as_ptr() as usize % 16 == 0,
+        "SoA stream slices must be aligned to 16 bytes"
+    );
+}
+
+#[test]
+fn test_ray_stream_resize() {
+    let mut rays = RayHitN::new(RayN::new(5));
+    rays.ray.set_org(
+        4,
+        Vector3 {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        },
+    );
+    rays.hit.set_geom_id(4, 0);
+    rays.resize(40);
+    assert_eq!(rays.len(), 40);
+    assert_eq!(
+        rays.ray.org(4),
+        Vector3 {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0
+        }
+    );
+    assert_eq!(rays.ray.tfar(39), f32::INFINITY);
+    assert!(!rays.hit.hit(39) && rays.hit.hit(4));
+
+    let rayhit = rays.as_rayhitnp();
+    assert_eq!(rayhit.ray.org_x as usize % 16, 0);
+    assert_eq!(rayhit.hit.geomID as usize % 16, 0);
+    rays.resize(3);
+    assert_eq!(rays.iter().count(), 3);
+    assert!(!rays.hit.any_hit());
+}
+
+#[test]
+fn test_ray_stream_compact() {
+    let mut rays = RayHitN::new(RayN::new(0));
+    for i in 0..6 {
+        rays.push(Ray::new(
+            Vector3 {
+                x: i as f32,
+                y: 0.0,
+                z: 0.0,
+            },
+            Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        ));
+    }
+    for i in 0..6 {
+        // Say the odd rays hit something and continue, the even rays terminate
+        if i % 2 == 1 {
+            rays.hit.set_geom_id(i, i as u32);
+        }
+    }
+    let remap = rays.compact(|_, hit| hit.hit());
+    assert_eq!(remap, vec![1, 3, 5]);
+    assert_eq!(rays.len(), 3);
+    assert_eq!(
+        rays.ray.org(1),
+        Vector3 {
+            x: 3.0,
+            y: 0.0,
+            z: 0.0
+        }
+    );
+    assert_eq!(rays.hit.geom_id(2), 5);
+
+    rays.reset_hits();
+    assert!(!rays.hit.any_hit());
+    rays.clear();
+    assert_eq!(rays.len(), 0);
+}
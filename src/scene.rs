@@ -1,79 +1,261 @@
+use mint::Vector3;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use std::marker::PhantomData;
-use std::mem;
+use std::os::raw;
+use std::{array, mem, ptr, slice};
 
+use aabb::Aabb;
+use collision::{self, Collision};
 use device::Device;
-use geometry::Geometry;
-use ray::{IntersectContext, Ray, RayHit};
-use ray_packet::{Ray4, RayHit4};
-use ray_stream::{RayHitN, RayN};
+use geometry::{self, EmbreeGeometry};
+use instance::Instance;
+use intersect_context::AsIntersectContext;
+use point_query::{
+    self, ClosestPoint, PointQuery, PointQuery16, PointQuery4, PointQuery8, PointQueryArgs,
+    PointQueryContext, SoAPointQuery,
+};
+use ray::{IntersectContext, Intersection, Ray, RayHit};
+use ray_packet::{
+    LaneCount, Ray16, Ray4, Ray8, RayHit16, RayHit4, RayHit8, RayHitPacket, RayPacket,
+    SupportedLaneCount, ValidMask,
+};
+use ray_stream::{RayHitN, RayHitNpRef, RayN, RayNpRef};
 use sys::*;
+use {BuildQuality, Error, GeometryType, SceneFlags};
+
+/// A geometry owned by the scene, along with the type it was
+/// attached as so it can be downcast back to it.
+struct AttachedGeometry<'a> {
+    geometry: Box<dyn EmbreeGeometry<'a> + Sync + 'a>,
+    type_id: TypeId,
+}
 
 /// A scene containing various geometry for rendering. Geometry
 /// can be added and removed by attaching and detaching it, after
 /// which the scene BVH can be built via `commit` which will
 /// return a `CommittedScene` which can be used for ray queries.
+///
+/// The `CommittedScene` borrows the scene, so it can't be modified while
+/// ray queries are being made. Once the `CommittedScene` is dropped the
+/// scene can be modified again, e.g. to update instance transforms or mesh
+/// vertices each frame of an animation, and committed again to rebuild
+/// only the parts of the BVH which changed.
 pub struct Scene<'a> {
     pub(crate) handle: RTCScene,
-    /// We don't need to actually keep a reference to the device,
-    /// we just need to track its lifetime for correctness
-    device: PhantomData<&'a Device>,
-    geometry: HashMap<u32, Geometry<'a>>,
+    device: &'a Device,
+    geometry: HashMap<u32, AttachedGeometry<'a>>,
+    backface_culling: bool,
 }
 
 impl<'a> Scene<'a> {
-    pub fn new(device: &'a Device) -> Scene {
+    pub fn new(device: &'a Device) -> Scene<'a> {
         Scene {
             handle: unsafe { rtcNewScene(device.handle) },
-            device: PhantomData,
+            device: device,
             geometry: HashMap::new(),
+            backface_culling: false,
+        }
+    }
+    /// Start building a new scene, to configure its flags and build
+    /// quality before geometry is attached and it's first committed.
+    pub fn builder(device: &'a Device) -> SceneBuilder<'a> {
+        SceneBuilder::new(device)
+    }
+    /// Set the flags of the scene, e.g. `DYNAMIC` for scenes which are
+    /// modified and recommitted often, or `ROBUST` to avoid optimizations
+    /// which reduce the arithmetic accuracy of ray queries. The flags take
+    /// effect the next time the scene is committed.
+    pub fn set_flags(&mut self, flags: SceneFlags) {
+        unsafe {
+            rtcSetSceneFlags(self.handle, flags);
+        }
+    }
+    /// Get the flags of the scene
+    pub fn flags(&self) -> SceneFlags {
+        unsafe { rtcGetSceneFlags(self.handle) }
+    }
+    /// Enable or disable `ROBUST` mode, which avoids optimizations that
+    /// reduce the arithmetic accuracy of ray queries, e.g. to not miss hits
+    /// on the edges shared by adjacent triangles.
+    pub fn set_robust(&mut self, robust: bool) {
+        let flags = self.flags();
+        if robust {
+            self.set_flags(flags | SceneFlags::ROBUST);
+        } else {
+            self.set_flags(SceneFlags(flags.0 & !SceneFlags::ROBUST.0));
+        }
+    }
+    pub fn is_robust(&self) -> bool {
+        self.flags().0 & SceneFlags::ROBUST.0 != 0
+    }
+    /// Ignore hits on the back faces of the scene's triangle and quad meshes.
+    /// If Embree was built with backface culling it's always enabled and this
+    /// has no effect, otherwise the filter function fallback of
+    /// `EmbreeGeometry::set_backface_culling` is set on the meshes attached
+    /// now and later. The meshes already attached must be committed again,
+    /// e.g. through `iter_mut`, before the scene is committed for it to take
+    /// effect on them.
+    pub fn set_backface_culling(&mut self, cull: bool) {
+        self.backface_culling = cull;
+        if !self.device.backface_culling_enabled() {
+            for g in self.geometry.values_mut() {
+                if is_mesh(g.geometry.handle()) {
+                    g.geometry.set_backface_culling(cull);
+                }
+            }
+        }
+    }
+    /// Check if hits on back faces are ignored, either by Embree's
+    /// culling or the filter function fallback
+    pub fn backface_culling(&self) -> bool {
+        self.backface_culling || self.device.backface_culling_enabled()
+    }
+    /// Set the quality of the top level BVH built over the scene's geometry.
+    /// `LOW` builds quickly for interactive editing, `HIGH` builds slowly but
+    /// makes ray queries faster. Takes effect the next time the scene is committed.
+    pub fn set_build_quality(&mut self, quality: BuildQuality) {
+        unsafe {
+            rtcSetSceneBuildQuality(self.handle, quality);
         }
     }
     /// Attach a new geometry to the scene. Returns the scene local ID which
     /// can than be used to find the hit geometry from the ray ID member.
     /// A geometry can only be attached to one Scene at a time, per the Embree
     /// documentation. The geometry can be detached from the scene to move
-    /// it to another one.
-    pub fn attach_geometry(&mut self, mesh: Geometry<'a>) -> u32 {
-        let id = unsafe { rtcAttachGeometry(self.handle, mesh.handle()) };
-        self.geometry.insert(id, mesh);
+    /// it to another one. The geometry must be `Sync` as queries on the
+    /// committed scene can call into it from multiple threads.
+    pub fn attach_geometry<G: EmbreeGeometry<'a> + Sync>(&mut self, mut geometry: G) -> u32 {
+        // The geometry is usually committed before it's attached, so commit it
+        // again for the culling filter to take effect
+        let cull = self.backface_culling && !self.device.backface_culling_enabled();
+        if cull && is_mesh(geometry.handle()) {
+            geometry.set_backface_culling(true);
+            geometry.commit();
+        }
+        let id = unsafe { rtcAttachGeometry(self.handle, geometry.handle()) };
+        self.geometry.insert(
+            id,
+            AttachedGeometry {
+                geometry: Box::new(geometry),
+                type_id: TypeId::of::<G::Static>(),
+            },
+        );
         id
     }
     /// Detach the geometry from the scene
-    pub fn deattach_geometry(&mut self, id: u32) -> Option<Geometry<'a>> {
-        self.geometry.remove(&id)
+    pub fn deattach_geometry(
+        &mut self,
+        id: u32,
+    ) -> Option<Box<dyn EmbreeGeometry<'a> + Sync + 'a>> {
+        self.geometry.remove(&id).map(|g| {
+            unsafe {
+                rtcDetachGeometry(self.handle, id);
+            }
+            g.geometry
+        })
     }
-    /// Look up a geometry in the scene by the ID returned from `attach_geometry`
-    pub fn get_geometry(&self, id: u32) -> Option<&Geometry<'a>> {
+    /// Look up a geometry in the scene by the ID returned from `attach_geometry`.
+    /// Returns `None` if there's no geometry with the ID or it isn't a `G`.
+    pub fn get_geometry<G: EmbreeGeometry<'a>>(&self, id: u32) -> Option<&G> {
         match self.geometry.get(&id) {
-            Some(g) => Some(g),
-            None => None,
+            Some(g) if g.type_id == TypeId::of::<G::Static>() => Some(unsafe {
+                &*(g.geometry.as_ref() as *const (dyn EmbreeGeometry<'a> + Sync) as *const G)
+            }),
+            _ => None,
         }
     }
-    /// Look up a geometry in the scene by the ID returned from `attach_geometry`
-    pub fn get_geometry_mut(&mut self, id: u32) -> Option<&mut Geometry<'a>> {
+    /// Look up a geometry in the scene by the ID returned from `attach_geometry`.
+    /// Returns `None` if there's no geometry with the ID or it isn't a `G`.
+    pub fn get_geometry_mut<G: EmbreeGeometry<'a>>(&mut self, id: u32) -> Option<&mut G> {
         match self.geometry.get_mut(&id) {
-            Some(g) => Some(g),
-            None => None,
+            Some(g) if g.type_id == TypeId::of::<G::Static>() => Some(unsafe {
+                &mut *(g.geometry.as_mut() as *mut (dyn EmbreeGeometry<'a> + Sync) as *mut G)
+            }),
+            _ => None,
         }
     }
-    /// Get an iterator over the geometry map
-    pub fn iter(&self) -> std::collections::hash_map::Iter<u32, Geometry<'a>> {
-        self.geometry.iter()
+    /// Get an iterator over the geometry in the scene and their IDs
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &(dyn EmbreeGeometry<'a> + Sync + 'a))> {
+        self.geometry
+            .iter()
+            .map(|(id, g)| (*id, g.geometry.as_ref()))
     }
-    /// Get an iterator over the geometry map
-    pub fn iter_mut(&mut self) -> std::collections::hash_map::IterMut<u32, Geometry<'a>> {
-        self.geometry.iter_mut()
+    /// Get an iterator over the geometry in the scene and their IDs
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (u32, &mut (dyn EmbreeGeometry<'a> + Sync + 'a))> {
+        self.geometry
+            .iter_mut()
+            .map(|(id, g)| (*id, g.geometry.as_mut()))
     }
     /// Commit the scene to build the BVH on top of the geometry to allow
     /// for ray tracing the scene. The returned `CommittedScene` can be
     /// used for intersection and occlusion tests. The `Scene` can't
-    /// be modified while the `CommittedScene` is active.
-    pub fn commit(&'a self) -> CommittedScene<'a> {
+    /// be modified while the `CommittedScene` is active, but can be
+    /// modified and committed again after it's dropped.
+    pub fn commit(&self) -> CommittedScene {
+        unsafe {
+            rtcCommitScene(self.handle);
+        }
+        CommittedScene::new(self)
+    }
+    /// Commit the scene cooperatively from multiple threads. Each thread
+    /// calling `join_commit` on the scene at the same time joins the BVH
+    /// build and returns once it's complete, allowing an application's own
+    /// thread pool to help with the build, e.g. through `rayon::broadcast`.
+    /// Join commit isn't supported by all Embree builds, which can be checked
+    /// with `Device::join_commit_supported`. When it's unsupported
+    /// `Error::INVALID_OPERATION` is returned without committing the scene.
+    pub fn join_commit(&self) -> Result<CommittedScene, Error> {
+        if !self.device.join_commit_supported() {
+            return Err(Error::INVALID_OPERATION);
+        }
+        // Errors are tracked per thread, so this only sees errors
+        // from this thread's part of the commit
+        self.device.get_error();
+        unsafe {
+            rtcJoinCommitScene(self.handle);
+        }
+        match self.device.get_error() {
+            Error::NONE => Ok(CommittedScene::new(self)),
+            err => Err(err),
+        }
+    }
+    /// Commit the scene, calling `progress` periodically during the BVH build
+    /// with the fraction of the build completed, between 0 and 1. Returning
+    /// `false` from `progress` cancels the build, in which case the error
+    /// reported by the device is returned instead of the `CommittedScene`.
+    /// The scene can be committed again after a cancelled build.
+    ///
+    /// `progress` is called from Embree's build threads, so must be `Sync`.
+    /// It's called from native code, so a panic inside it aborts the process.
+    pub fn commit_with_progress<F>(&self, progress: F) -> Result<CommittedScene, Error>
+    where
+        F: Fn(f64) -> bool + Sync,
+    {
+        unsafe extern "C" fn monitor<F>(ptr: *mut raw::c_void, n: f64) -> bool
+        where
+            F: Fn(f64) -> bool + Sync,
+        {
+            let progress = &*(ptr as *const F);
+            progress(n)
+        }
+
+        // Clear any earlier error so we only report ones from this commit
+        self.device.get_error();
         unsafe {
+            rtcSetSceneProgressMonitorFunction(
+                self.handle,
+                Some(monitor::<F>),
+                &progress as *const F as *mut raw::c_void,
+            );
             rtcCommitScene(self.handle);
+            rtcSetSceneProgressMonitorFunction(self.handle, None, ptr::null_mut());
+        }
+        match self.device.get_error() {
+            Error::NONE => Ok(CommittedScene::new(self)),
+            err => Err(err),
         }
-        CommittedScene { scene: &self }
     }
     /// Get the underlying handle to the scene, e.g. for passing it to
     /// native code or ISPC kernels.
@@ -82,6 +264,57 @@ impl<'a> Scene<'a> {
     }
 }
 
+/// Builder for configuring a `Scene` before it's first committed.
+/// Scenes are created with no flags and `MEDIUM` build quality by default.
+pub struct SceneBuilder<'a> {
+    device: &'a Device,
+    flags: SceneFlags,
+    build_quality: BuildQuality,
+    backface_culling: bool,
+}
+
+impl<'a> SceneBuilder<'a> {
+    pub fn new(device: &'a Device) -> SceneBuilder<'a> {
+        SceneBuilder {
+            device: device,
+            flags: SceneFlags::NONE,
+            build_quality: BuildQuality::MEDIUM,
+            backface_culling: false,
+        }
+    }
+    /// Set the flags of the scene, see `Scene::set_flags`
+    pub fn flags(mut self, flags: SceneFlags) -> SceneBuilder<'a> {
+        self.flags = flags;
+        self
+    }
+    /// Enable or disable `ROBUST` mode, see `Scene::set_robust`
+    pub fn robust(mut self, robust: bool) -> SceneBuilder<'a> {
+        if robust {
+            self.flags = self.flags | SceneFlags::ROBUST;
+        } else {
+            self.flags = SceneFlags(self.flags.0 & !SceneFlags::ROBUST.0);
+        }
+        self
+    }
+    /// Ignore hits on back faces, see `Scene::set_backface_culling`
+    pub fn backface_culling(mut self, cull: bool) -> SceneBuilder<'a> {
+        self.backface_culling = cull;
+        self
+    }
+    /// Set the build quality of the scene, see `Scene::set_build_quality`
+    pub fn build_quality(mut self, quality: BuildQuality) -> SceneBuilder<'a> {
+        self.build_quality = quality;
+        self
+    }
+    pub fn build(self) -> Scene<'a> {
+        let mut scene = Scene::new(self.device);
+        scene.set_flags(self.flags);
+        scene.set_build_quality(self.build_quality);
+        scene.set_backface_culling(self.backface_culling);
+        scene
+    }
+}
+
 impl<'a> Drop for Scene<'a> {
     fn drop(&mut self) {
         unsafe {
@@ -92,119 +325,618 @@ impl<'a> Drop for Scene<'a> {
 
 unsafe impl<'a> Sync for Scene<'a> {}
 
+/// Check if the geometry is a triangle or quad mesh, the only geometry
+/// the backface culling fallback is set on
+fn is_mesh(handle: RTCGeometry) -> bool {
+    let data = unsafe { geometry::geometry_data(handle) };
+    matches!(
+        data.and_then(|d| d.geometry_type),
+        Some(GeometryType::TRIANGLE) | Some(GeometryType::QUAD)
+    )
+}
+
+/// Looks up the geometry attached to a scene, independent of the
+/// lifetime of the device and buffers the scene borrows.
+trait AttachedGeometries {
+    fn device(&self) -> &Device;
+    fn attached_handle(&self, id: u32) -> Option<RTCGeometry>;
+    fn instanced_scene(&self, inst_id: u32) -> Option<&CommittedScene<'_>>;
+}
+
+impl<'a> AttachedGeometries for Scene<'a> {
+    fn device(&self) -> &Device {
+        self.device
+    }
+    fn attached_handle(&self, id: u32) -> Option<RTCGeometry> {
+        self.geometry.get(&id).map(|g| g.geometry.handle())
+    }
+    fn instanced_scene(&self, inst_id: u32) -> Option<&CommittedScene<'_>> {
+        self.get_geometry::<Instance>(inst_id)
+            .map(|i| i.instanced_scene())
+    }
+}
+
 /// A committed scene with a BVH built over the geometry
 /// which can be used for ray queries.
 pub struct CommittedScene<'a> {
-    pub(crate) scene: &'a Scene<'a>,
+    pub(crate) handle: RTCScene,
+    /// The scene is borrowed while it's committed to prevent
+    /// modifying it while it's being used for ray queries
+    scene: &'a (dyn AttachedGeometries + 'a),
 }
 
 impl<'a> CommittedScene<'a> {
-    pub fn intersect(&self, ctx: &mut IntersectContext, ray: &mut RayHit) {
+    fn new<'s>(scene: &'a Scene<'s>) -> CommittedScene<'a> {
+        CommittedScene {
+            handle: scene.handle,
+            scene: scene,
+        }
+    }
+    pub fn intersect<C: AsIntersectContext>(&self, ctx: &mut C, ray: &mut RayHit) {
         unsafe {
-            rtcIntersect1(
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
-                ray as *mut RTCRayHit,
-            );
+            rtcIntersect1(self.handle, ctx.as_raw(), ray as *mut RTCRayHit);
         }
     }
-    pub fn occluded(&self, ctx: &mut IntersectContext, ray: &mut Ray) {
+    /// Find the closest hit along `ray`, if any, using an incoherent context
+    pub fn intersect_ray(&self, ray: Ray) -> Option<Intersection> {
+        let mut ray_hit = RayHit::new(ray);
+        self.intersect(&mut IntersectContext::incoherent(), &mut ray_hit);
+        ray_hit.intersection()
+    }
+    /// Check if anything is hit along `ray`, using an incoherent context
+    pub fn occluded_ray(&self, ray: Ray) -> bool {
+        let mut ray = ray;
+        self.occluded(&mut IntersectContext::incoherent(), &mut ray);
+        ray.tfar == f32::NEG_INFINITY
+    }
+    pub fn occluded<C: AsIntersectContext>(&self, ctx: &mut C, ray: &mut Ray) {
         unsafe {
-            rtcOccluded1(
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
-                ray as *mut RTCRay,
-            );
+            rtcOccluded1(self.handle, ctx.as_raw(), ray as *mut RTCRay);
         }
     }
-    pub fn intersect4(&self, ctx: &mut IntersectContext, ray: &mut RayHit4, valid: &[i32; 4]) {
+    pub fn intersect4<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        ray: &mut RayHit4,
+        valid: &ValidMask<4>,
+    ) {
         unsafe {
             rtcIntersect4(
-                valid.as_ptr(),
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
                 ray as *mut RTCRayHit4,
             );
         }
     }
-    pub fn occluded4(&self, ctx: &mut IntersectContext, ray: &mut Ray4, valid: &[i32; 4]) {
+    pub fn occluded4<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        ray: &mut Ray4,
+        valid: &ValidMask<4>,
+    ) {
         unsafe {
             rtcOccluded4(
-                valid.as_ptr(),
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
                 ray as *mut RTCRay4,
             );
         }
     }
-    pub fn intersect_stream_aos(&self, ctx: &mut IntersectContext, rays: &mut Vec<RayHit>) {
+    pub fn intersect8<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        ray: &mut RayHit8,
+        valid: &ValidMask<8>,
+    ) {
+        unsafe {
+            rtcIntersect8(
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
+                ray as *mut RTCRayHit8,
+            );
+        }
+    }
+    pub fn occluded8<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        ray: &mut Ray8,
+        valid: &ValidMask<8>,
+    ) {
+        unsafe {
+            rtcOccluded8(
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
+                ray as *mut RTCRay8,
+            );
+        }
+    }
+    pub fn intersect16<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        ray: &mut RayHit16,
+        valid: &ValidMask<16>,
+    ) {
+        unsafe {
+            rtcIntersect16(
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
+                ray as *mut RTCRayHit16,
+            );
+        }
+    }
+    pub fn occluded16<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        ray: &mut Ray16,
+        valid: &ValidMask<16>,
+    ) {
+        unsafe {
+            rtcOccluded16(
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
+                ray as *mut RTCRay16,
+            );
+        }
+    }
+    /// Intersect a packet of `N` rays with the scene, where `N` is one of the
+    /// packet widths supported by Embree. Only the rays active in `valid` are traced.
+    pub fn intersect_packet<C: AsIntersectContext, const N: usize>(
+        &self,
+        ctx: &mut C,
+        ray: &mut RayHitPacket<N>,
+        valid: &ValidMask<N>,
+    ) where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        unsafe {
+            LaneCount::<N>::intersect(
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
+                ray as *mut RayHitPacket<N>,
+            );
+        }
+    }
+    /// Test a packet of `N` rays for occlusion, see `intersect_packet`
+    pub fn occluded_packet<C: AsIntersectContext, const N: usize>(
+        &self,
+        ctx: &mut C,
+        ray: &mut RayPacket<N>,
+        valid: &ValidMask<N>,
+    ) where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        unsafe {
+            LaneCount::<N>::occluded(
+                valid.as_array().as_ptr(),
+                self.handle,
+                ctx.as_raw(),
+                ray as *mut RayPacket<N>,
+            );
+        }
+    }
+    pub fn intersect_stream_aos<C: AsIntersectContext>(&self, ctx: &mut C, rays: &mut [RayHit]) {
         let m = rays.len();
         unsafe {
             rtcIntersect1M(
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
+                self.handle,
+                ctx.as_raw(),
                 rays.as_mut_ptr(),
                 m as u32,
                 mem::size_of::<RayHit>(),
             );
         }
     }
-    pub fn occluded_stream_aos(&self, ctx: &mut IntersectContext, rays: &mut Vec<Ray>) {
+    pub fn occluded_stream_aos<C: AsIntersectContext>(&self, ctx: &mut C, rays: &mut [Ray]) {
         let m = rays.len();
         unsafe {
             rtcOccluded1M(
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
+                self.handle,
+                ctx.as_raw(),
                 rays.as_mut_ptr(),
                 m as u32,
                 mem::size_of::<Ray>(),
             );
         }
     }
-    pub fn intersect_stream_soa(&self, ctx: &mut IntersectContext, rays: &mut RayHitN) {
+    pub fn intersect_stream_soa<C: AsIntersectContext>(&self, ctx: &mut C, rays: &mut RayHitN) {
+        self.intersect_stream_soa_ref(ctx, &mut rays.np_ref());
+    }
+    pub fn occluded_stream_soa<C: AsIntersectContext>(&self, ctx: &mut C, rays: &mut RayN) {
+        self.occluded_stream_soa_ref(ctx, &mut rays.np_ref());
+    }
+    /// Intersect a ray stream in SoA format stored in borrowed slices
+    pub fn intersect_stream_soa_ref<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        rays: &mut RayHitNpRef,
+    ) {
         let n = rays.len();
+        let mut rayhit = rays.as_rayhitnp();
         unsafe {
-            let mut rayhit = rays.as_rayhitnp();
             rtcIntersectNp(
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
+                self.handle,
+                ctx.as_raw(),
                 &mut rayhit as *mut RTCRayHitNp,
                 n as u32,
             );
         }
     }
-    pub fn occluded_stream_soa(&self, ctx: &mut IntersectContext, rays: &mut RayN) {
+    /// Test a ray stream in SoA format stored in borrowed slices for occlusion
+    pub fn occluded_stream_soa_ref<C: AsIntersectContext>(&self, ctx: &mut C, rays: &mut RayNpRef) {
         let n = rays.len();
+        let mut r = rays.as_raynp();
         unsafe {
-            let mut r = rays.as_raynp();
-            rtcOccludedNp(
-                self.scene.handle,
-                ctx as *mut RTCIntersectContext,
-                &mut r as *mut RTCRayNp,
-                n as u32,
+            rtcOccludedNp(self.handle, ctx.as_raw(), &mut r as *mut RTCRayNp, n as u32);
+        }
+    }
+    /// Intersect a stream of rays given by references into a larger pool of
+    /// rays, to trace a sparse subset of the pool without compacting it.
+    pub fn intersect_stream_ptrs<C: AsIntersectContext>(
+        &self,
+        ctx: &mut C,
+        rays: &mut [&mut RayHit],
+    ) {
+        let m = rays.len();
+        unsafe {
+            rtcIntersect1Mp(
+                self.handle,
+                ctx.as_raw(),
+                rays.as_mut_ptr() as *mut *mut RTCRayHit,
+                m as u32,
+            );
+        }
+    }
+    /// Test a stream of rays given by references for occlusion,
+    /// see `intersect_stream_ptrs`
+    pub fn occluded_stream_ptrs<C: AsIntersectContext>(&self, ctx: &mut C, rays: &mut [&mut Ray]) {
+        let m = rays.len();
+        unsafe {
+            rtcOccluded1Mp(
+                self.handle,
+                ctx.as_raw(),
+                rays.as_mut_ptr() as *mut *mut RTCRay,
+                m as u32,
+            );
+        }
+    }
+    /// Intersect a stream of ray packets of width `N` with the scene. All rays
+    /// in the packets are traced, rays can be disabled by setting their
+    /// `tnear` greater than their `tfar`.
+    pub fn intersect_stream_packets<C: AsIntersectContext, const N: usize>(
+        &self,
+        ctx: &mut C,
+        rays: &mut [RayHitPacket<N>],
+    ) where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        let m = rays.len();
+        unsafe {
+            rtcIntersectNM(
+                self.handle,
+                ctx.as_raw(),
+                rays.as_mut_ptr() as *mut RTCRayHitN,
+                N as u32,
+                m as u32,
+                mem::size_of::<RayHitPacket<N>>(),
             );
         }
     }
-    pub fn bounds(&self) -> RTCBounds {
-        let mut bounds = RTCBounds {
-            lower_x: 0.0,
-            upper_x: 0.0,
-            lower_y: 0.0,
-            upper_y: 0.0,
-            lower_z: 0.0,
-            upper_z: 0.0,
-            align0: 0.0,
-            align1: 0.0,
+    /// Test a stream of ray packets of width `N` for occlusion,
+    /// see `intersect_stream_packets`
+    pub fn occluded_stream_packets<C: AsIntersectContext, const N: usize>(
+        &self,
+        ctx: &mut C,
+        rays: &mut [RayPacket<N>],
+    ) where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        let m = rays.len();
+        unsafe {
+            rtcOccludedNM(
+                self.handle,
+                ctx.as_raw(),
+                rays.as_mut_ptr() as *mut RTCRayN,
+                N as u32,
+                m as u32,
+                mem::size_of::<RayPacket<N>>(),
+            );
+        }
+    }
+    /// Find the geometry within `radius` of `point`. The `callback` is
+    /// called for each primitive whose bounds overlap the query, and can
+    /// shrink the query radius through the arguments to cull primitives further
+    /// than the closest found so far, returning `true` if it did so.
+    /// Returns `true` if the query radius was changed by any callback.
+    pub fn point_query<V, F>(&self, point: V, radius: f32, callback: F) -> bool
+    where
+        V: Into<Vector3<f32>>,
+        F: FnMut(&mut PointQueryArgs) -> bool,
+    {
+        struct QueryData<F> {
+            scene: RTCScene,
+            callback: F,
+        }
+        unsafe extern "C" fn query_fn<F>(args: *mut RTCPointQueryFunctionArguments) -> bool
+        where
+            F: FnMut(&mut PointQueryArgs) -> bool,
+        {
+            let data = &mut *((*args).userPtr as *mut QueryData<F>);
+            let mut args = PointQueryArgs::new(&mut *args, data.scene);
+            (data.callback)(&mut args)
+        }
+
+        let mut query = PointQuery::new(point, radius);
+        let mut ctx = PointQueryContext::new();
+        let mut data = QueryData {
+            scene: self.handle,
+            callback: callback,
         };
         unsafe {
-            rtcGetSceneBounds(self.handle(), &mut bounds as *mut RTCBounds);
+            rtcPointQuery(
+                self.handle,
+                &mut query as *mut RTCPointQuery,
+                &mut ctx as *mut RTCPointQueryContext,
+                Some(query_fn::<F>),
+                &mut data as *mut QueryData<F> as *mut raw::c_void,
+            )
+        }
+    }
+    /// Find the closest point within `radius` of `point` on the triangle and
+    /// quad meshes in the scene, including those in instanced scenes.
+    /// Other geometry types are ignored. Returns `None` if there's
+    /// no geometry within the radius.
+    pub fn closest_point<V: Into<Vector3<f32>>>(
+        &self,
+        point: V,
+        radius: f32,
+    ) -> Option<ClosestPoint> {
+        let mut closest = None;
+        self.point_query(point, radius, |args| {
+            point_query::update_closest_point(args, &mut closest, radius)
+        });
+        closest
+    }
+    /// Make a packet of point queries, see `point_query`. Only the queries
+    /// active in `valid` are made. The `callback` is passed the index
+    /// of the query in the packet along with the query arguments.
+    pub fn point_query4<F>(
+        &self,
+        valid: &ValidMask<4>,
+        query: &mut PointQuery4,
+        callback: F,
+    ) -> bool
+    where
+        F: FnMut(usize, &mut PointQueryArgs) -> bool,
+    {
+        unsafe { self.point_query_packet(valid, query, callback, rtcPointQuery4) }
+    }
+    /// Make a packet of point queries, see `point_query4`
+    pub fn point_query8<F>(
+        &self,
+        valid: &ValidMask<8>,
+        query: &mut PointQuery8,
+        callback: F,
+    ) -> bool
+    where
+        F: FnMut(usize, &mut PointQueryArgs) -> bool,
+    {
+        unsafe { self.point_query_packet(valid, query, callback, rtcPointQuery8) }
+    }
+    /// Make a packet of point queries, see `point_query4`
+    pub fn point_query16<F>(
+        &self,
+        valid: &ValidMask<16>,
+        query: &mut PointQuery16,
+        callback: F,
+    ) -> bool
+    where
+        F: FnMut(usize, &mut PointQueryArgs) -> bool,
+    {
+        unsafe { self.point_query_packet(valid, query, callback, rtcPointQuery16) }
+    }
+    /// Find the closest point on the triangle and quad meshes in the scene
+    /// for each valid query in the packet, see `closest_point`.
+    pub fn closest_point4(
+        &self,
+        valid: &ValidMask<4>,
+        query: &PointQuery4,
+    ) -> [Option<ClosestPoint>; 4] {
+        let mut closest = [None; 4];
+        let mut q = *query;
+        self.point_query4(valid, &mut q, |i, args| {
+            point_query::update_closest_point(args, &mut closest[i], query.radius(i))
+        });
+        closest
+    }
+    /// Find the closest points for a packet of queries, see `closest_point4`
+    pub fn closest_point8(
+        &self,
+        valid: &ValidMask<8>,
+        query: &PointQuery8,
+    ) -> [Option<ClosestPoint>; 8] {
+        let mut closest = [None; 8];
+        let mut q = *query;
+        self.point_query8(valid, &mut q, |i, args| {
+            point_query::update_closest_point(args, &mut closest[i], query.radius(i))
+        });
+        closest
+    }
+    /// Find the closest points for a packet of queries, see `closest_point4`
+    pub fn closest_point16(
+        &self,
+        valid: &ValidMask<16>,
+        query: &PointQuery16,
+    ) -> [Option<ClosestPoint>; 16] {
+        let mut closest = [None; 16];
+        let mut q = *query;
+        self.point_query16(valid, &mut q, |i, args| {
+            point_query::update_closest_point(args, &mut closest[i], query.radius(i))
+        });
+        closest
+    }
+    /// Run a packet point query through one of the `rtcPointQuery4/8/16` kernels.
+    /// Embree takes a user pointer per query, which we use to tell the
+    /// callback which query in the packet it's called for.
+    unsafe fn point_query_packet<Q, F, const N: usize>(
+        &self,
+        valid: &ValidMask<N>,
+        query: &mut Q,
+        mut callback: F,
+        kernel: unsafe extern "C" fn(
+            *const raw::c_int,
+            RTCScene,
+            *mut Q,
+            *mut RTCPointQueryContext,
+            RTCPointQueryFunction,
+            *mut *mut raw::c_void,
+        ) -> bool,
+    ) -> bool
+    where
+        F: FnMut(usize, &mut PointQueryArgs) -> bool,
+    {
+        struct QueryData<F> {
+            scene: RTCScene,
+            index: usize,
+            callback: *mut F,
+        }
+        unsafe extern "C" fn query_fn<F>(args: *mut RTCPointQueryFunctionArguments) -> bool
+        where
+            F: FnMut(usize, &mut PointQueryArgs) -> bool,
+        {
+            let data = &*((*args).userPtr as *const QueryData<F>);
+            let mut args = PointQueryArgs::new(&mut *args, data.scene);
+            (*data.callback)(data.index, &mut args)
+        }
+
+        let callback = &mut callback as *mut F;
+        let mut data: [QueryData<F>; N] = array::from_fn(|i| QueryData {
+            scene: self.handle,
+            index: i,
+            callback: callback,
+        });
+        let mut user_ptrs: [*mut raw::c_void; N] =
+            array::from_fn(|i| &mut data[i] as *mut QueryData<F> as *mut raw::c_void);
+        let mut ctx = PointQueryContext::new();
+        kernel(
+            valid.as_array().as_ptr(),
+            self.handle,
+            query as *mut Q,
+            &mut ctx as *mut RTCPointQueryContext,
+            Some(query_fn::<F>),
+            user_ptrs.as_mut_ptr(),
+        )
+    }
+    /// Get the bounds of the scene
+    pub fn bounds(&self) -> Aabb {
+        let mut bounds: RTCBounds = Aabb::empty().into();
+        unsafe {
+            rtcGetSceneBounds(self.handle, &mut bounds as *mut RTCBounds);
         }
-        bounds
+        bounds.into()
+    }
+    /// Get the linear bounds of a scene with motion blur, returned as the
+    /// bounds at the start and end of the time range. Linearly interpolating
+    /// between the two bounds conservatively bounds the scene at any time.
+    pub fn linear_bounds(&self) -> (Aabb, Aabb) {
+        let mut bounds = RTCLinearBounds {
+            bounds0: Aabb::empty().into(),
+            bounds1: Aabb::empty().into(),
+        };
+        unsafe {
+            rtcGetSceneLinearBounds(self.handle, &mut bounds as *mut RTCLinearBounds);
+        }
+        (bounds.bounds0.into(), bounds.bounds1.into())
+    }
+    /// Find the pairs of primitives in this scene and `other` whose bounds
+    /// overlap. The `callback` is called with batches of collisions as
+    /// they're found. Passing the scene itself as `other` finds
+    /// self-collisions. The overlap is conservative, so pairs can be passed
+    /// through `triangles_intersect` to keep only the triangles which
+    /// actually intersect.
+    ///
+    /// Embree can only collide scenes made of user geometry, e.g. the
+    /// `CollisionMesh` of each triangle mesh, otherwise the device's error is
+    /// returned. `callback` is called from Embree's build threads, so must be
+    /// `Sync`. It's called from native code, so a panic inside it aborts the
+    /// process.
+    pub fn collide<F>(&self, other: &CommittedScene, callback: F) -> Result<(), Error>
+    where
+        F: Fn(&[Collision]) + Sync,
+    {
+        unsafe extern "C" fn collide_fn<F>(
+            user_ptr: *mut raw::c_void,
+            collisions: *mut RTCCollision,
+            num_collisions: raw::c_uint,
+        ) where
+            F: Fn(&[Collision]) + Sync,
+        {
+            let callback = &*(user_ptr as *const F);
+            if num_collisions > 0 {
+                callback(slice::from_raw_parts(collisions, num_collisions as usize));
+            }
+        }
+
+        let device = self.scene.device();
+        // Clear any earlier error so we only report ones from this query
+        device.get_error();
+        unsafe {
+            rtcCollide(
+                self.handle,
+                other.handle,
+                Some(collide_fn::<F>),
+                &callback as *const F as *mut raw::c_void,
+            );
+        }
+        match device.get_error() {
+            Error::NONE => Ok(()),
+            err => Err(err),
+        }
+    }
+    /// Check if the primitives of a collision between this scene and `other`
+    /// found by `collide` actually intersect. Returns `None` if either
+    /// primitive isn't part of a `TriangleMesh` or its `CollisionMesh`.
+    pub fn triangles_intersect(
+        &self,
+        other: &CommittedScene,
+        collision: &Collision,
+    ) -> Option<bool> {
+        let geom0 = self.scene.attached_handle(collision.geomID0)?;
+        let geom1 = other.scene.attached_handle(collision.geomID1)?;
+        unsafe {
+            let a = collision::mesh_triangle(geom0, collision.primID0)?;
+            let b = collision::mesh_triangle(geom1, collision.primID1)?;
+            Some(collision::separating_axis_test(&a, &b))
+        }
+    }
+    /// Look up the user data attached to a geometry in the scene by its ID,
+    /// e.g. the `geom_id` of a hit on geometry which isn't instanced. The
+    /// `geom_id` of a hit on instanced geometry is an ID in the instanced
+    /// scene, so `instance_user_data` must be used to look it up, while its
+    /// `inst_id` can be passed here to find the user data of the instance hit.
+    /// Returns `None` if there's no geometry attached with the ID, it has no
+    /// user data or the user data isn't a `T`.
+    pub fn user_data<T: Any>(&self, id: u32) -> Option<&T> {
+        let geom = self.scene.attached_handle(id)?;
+        unsafe { geometry::user_data(geom) }
+    }
+    /// Look up the user data attached to geometry `geom_id` of the scene
+    /// instanced by the `Instance` attached with ID `inst_id`, e.g. the IDs of
+    /// a hit on instanced geometry. Returns `None` if `inst_id` isn't an
+    /// attached `Instance` or the lookup in its scene fails, see `user_data`.
+    pub fn instance_user_data<T: Any>(&self, inst_id: u32, geom_id: u32) -> Option<&T> {
+        self.scene.instanced_scene(inst_id)?.user_data(geom_id)
     }
     /// Get the underlying handle to the scene, e.g. for passing it to
     /// native code or ISPC kernels.
     pub unsafe fn handle(&self) -> RTCScene {
-        self.scene.handle
+        self.handle
     }
 }
 
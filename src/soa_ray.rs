@@ -2,7 +2,7 @@ use std::iter::{ExactSizeIterator, Iterator};
 use std::marker::PhantomData;
 use std::u32;
 
-use cgmath::Vector3;
+use mint::Vector3;
 
 pub trait SoARay {
     fn org(&self, i: usize) -> Vector3<f32>;
@@ -58,6 +58,9 @@ pub struct SoARayRef<'a, T> {
 }
 
 impl<'a, T: SoARay + 'a> SoARayRef<'a, T> {
+    pub(crate) fn new(ray: &'a T, idx: usize) -> SoARayRef<'a, T> {
+        SoARayRef { ray: ray, idx: idx }
+    }
     pub fn origin(&self) -> Vector3<f32> {
         self.ray.org(self.idx)
     }
@@ -93,17 +96,17 @@ impl<'a, T: SoARay + 'a> SoARayRefMut<'a, T> {
         let ray = unsafe { self.ray.as_ref().expect("should never be null!") };
         ray.org(self.idx)
     }
-    pub fn set_origin(&mut self, o: Vector3<f32>) {
+    pub fn set_origin<V: Into<Vector3<f32>>>(&mut self, o: V) {
         let ray = unsafe { self.ray.as_mut().expect("should never be null!") };
-        ray.set_org(self.idx, o);
+        ray.set_org(self.idx, o.into());
     }
     pub fn dir(&self) -> Vector3<f32> {
         let ray = unsafe { self.ray.as_ref().expect("should never be null!") };
         ray.dir(self.idx)
     }
-    pub fn set_dir(&mut self, d: Vector3<f32>) {
+    pub fn set_dir<V: Into<Vector3<f32>>>(&mut self, d: V) {
         let ray = unsafe { self.ray.as_mut().expect("should never be null!") };
-        ray.set_dir(self.idx, d);
+        ray.set_dir(self.idx, d.into());
     }
     pub fn tnear(&self) -> f32 {
         let ray = unsafe { self.ray.as_ref().expect("should never be null!") };
@@ -232,6 +235,9 @@ pub struct SoAHitRef<'a, T> {
 }
 
 impl<'a, T: SoAHit + 'a> SoAHitRef<'a, T> {
+    pub(crate) fn new(hit: &'a T, idx: usize) -> SoAHitRef<'a, T> {
+        SoAHitRef { hit: hit, idx: idx }
+    }
     pub fn normal(&self) -> Vector3<f32> {
         self.hit.normal(self.idx)
     }
@@ -302,9 +308,9 @@ impl<'a, T: SoAHit + 'a> SoAHitRefMut<'a, T> {
         let hit = unsafe { self.hit.as_ref().expect("should never be null!") };
         hit.normal(self.idx)
     }
-    pub fn set_normal(&mut self, n: Vector3<f32>) {
+    pub fn set_normal<V: Into<Vector3<f32>>>(&mut self, n: V) {
         let hit = unsafe { self.hit.as_mut().expect("should never be null!") };
-        hit.set_normal(self.idx, n)
+        hit.set_normal(self.idx, n.into())
     }
     pub fn uv(&self) -> (f32, f32) {
         let hit = unsafe { self.hit.as_ref().expect("should never be null!") };
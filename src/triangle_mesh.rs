@@ -1,14 +1,15 @@
-use cgmath::{Vector3, Vector4};
+use mint::{Vector3, Vector4};
 
 use buffer::Buffer;
 use device::Device;
-use geometry::Geometry;
+use geometry::{self, EmbreeGeometry};
 use sys::*;
 use {BufferType, Format, GeometryType};
 
 pub struct TriangleMesh<'a> {
     device: &'a Device,
     pub(crate) handle: RTCGeometry,
+    pub(crate) num_tris: usize,
     pub vertex_buffer: Buffer<'a, Vector4<f32>>,
     pub index_buffer: Buffer<'a, Vector3<u32>>,
 }
@@ -42,14 +43,35 @@ impl<'a> TriangleMesh<'a> {
                 num_tris,
             );
             index_buffer.set_attachment(h, BufferType::INDEX, 0);
+
+            // Tag the mesh so closest point queries can find its buffers
+            geometry::geometry_data_mut(h).geometry_type = Some(GeometryType::TRIANGLE);
         }
         TriangleMesh {
             device: device,
             handle: h,
+            num_tris: num_tris,
             vertex_buffer: vertex_buffer,
             index_buffer: index_buffer,
         }
     }
 }
 
+unsafe impl<'a> EmbreeGeometry<'a> for TriangleMesh<'a> {
+    type Static = TriangleMesh<'static>;
+
+    fn handle(&self) -> RTCGeometry {
+        self.handle
+    }
+}
+
+impl<'a> Drop for TriangleMesh<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            geometry::release_user_data(self.handle);
+            rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
 unsafe impl<'a> Sync for TriangleMesh<'a> {}